@@ -27,6 +27,9 @@ class Countdown extends EventTarget {
     #diff_duration;
     /** @type {number} */
     #timeout;
+    /** Milliseconds to add to the local clock to get the server's time.
+     * @type {number} */
+    #clock_offset;
     /** @type {number | null} */
     interval_id;
 
@@ -34,6 +37,7 @@ class Countdown extends EventTarget {
     constructor(datetime_target) {
         super();
         this.#datetime_target = datetime_target;
+        this.#clock_offset = 0;
         this.#datetime_now = new Date();
         this.#diff_duration = getDuration(
             this.#datetime_now,
@@ -92,7 +96,7 @@ class Countdown extends EventTarget {
     }
 
     #intervalUpdate() {
-        this.#datetime_now = new Date();
+        this.#datetime_now = new Date(Date.now() + this.#clock_offset);
         const new_diff_duration = getDuration(
             this.#datetime_now,
             this.#datetime_target,
@@ -154,6 +158,11 @@ class Countdown extends EventTarget {
         this.#timeout = timeout;
     }
 
+    /** @param {number} clock_offset */
+    setClockOffset(clock_offset) {
+        this.#clock_offset = clock_offset;
+    }
+
     /** @param {Object} new_datetime_target */
     updateDatetimeTarget(new_datetime_target) {
         this.#datetime_target = new_datetime_target;
@@ -452,6 +461,14 @@ export class CountdownDisplay {
         }
     }
 
+    /**
+     * Corrects the countdown for the local clock being off from the server's.
+     * @param {number} clock_offset
+     */
+    setClockOffset(clock_offset) {
+        this.countdown.setClockOffset(clock_offset);
+    }
+
     /** @param {Object} new_datetime_target */
     updateDatetimeTarget(new_datetime_target) {
         this.countdown.updateDatetimeTarget(new_datetime_target);
@@ -3,31 +3,65 @@
 
 import { Timeout } from "./utils/timeout";
 
+/** Seconds to wait before reconnecting after the server closes the connection
+ * because it's restarting. */
+const RESTART_RECONNECT_SECONDS = 5;
+/** Seconds between syncing the clock with the server's. */
+const TIME_SYNC_INTERVAL_SECONDS = 60;
+/** Number of times the websocket can fail to connect, without ever having
+ * opened, before falling back to server-sent events. Some proxies block
+ * websockets. */
+const ATTEMPTS_BEFORE_FALLBACK = 2;
+
+/** Connection to a page's websocket, which falls back to server-sent events
+ * (with clicks sent as POST requests) if websockets are blocked. */
 export class CustomWebSocket extends EventTarget {
     /** @type {WebSocket | null} */
     #websocket;
+    /** Only used once falling back to server-sent events.
+     * @type {EventSource | null} */
+    #event_source;
+    /** @type {boolean} */
+    #use_event_source;
+    /** @type {boolean} */
+    #has_opened;
     /** @type {Timeout} */
     #disconnect_timeout;
     /** @type {Timeout} */
     #reconnect_timeout;
     /** @type {number} */
     #reconnect_retries;
+    /** @type {number | null} */
+    #time_sync_interval_id;
+    /** Milliseconds to add to the local clock to get the server's time, or
+     * null if it hasn't been measured yet.
+     * @type {number | null} */
+    clock_offset;
     /** @type {string} */
-    url;
+    page_name;
 
-    /** @param {string} url */
-    constructor(url) {
+    /** @param {string} page_name */
+    constructor(page_name) {
         super();
-        this.url = url;
+        this.page_name = page_name;
+        this.#event_source = null;
+        this.#use_event_source = false;
+        this.#has_opened = false;
         this.#disconnect_timeout = new Timeout(null);
         this.#reconnect_timeout = new Timeout(this.tryConnect.bind(this));
         this.#reconnect_retries = 0;
+        this.#time_sync_interval_id = null;
+        this.clock_offset = null;
         this.#websocket = null;
         this.#connect();
     }
 
     #connect() {
-        this.#websocket = new WebSocket(this.url);
+        if (this.#use_event_source) {
+            this.#connectEventSource();
+            return;
+        }
+        this.#websocket = new WebSocket(`/${this.page_name}/websocket`);
         this.#websocket.binaryType = "arraybuffer";
 
         this.#websocket.addEventListener("open", this.#onOpen.bind(this));
@@ -36,15 +70,104 @@ export class CustomWebSocket extends EventTarget {
         this.#websocket.addEventListener("error", this.#onError.bind(this));
     }
 
+    #connectEventSource() {
+        const event_source = new EventSource(`/${this.page_name}/events`);
+        this.#event_source = event_source;
+
+        event_source.addEventListener("open", this.#onOpen.bind(this));
+        event_source.addEventListener("datetime", (event) => {
+            this.dispatchEvent(
+                new CustomEvent("updatedatetime", {
+                    // Unix timestamp in milliseconds
+                    detail: new Date(Number(event.data)),
+                }),
+            );
+        });
+        event_source.addEventListener("click", (event) => {
+            const msg = JSON.parse(event.data);
+            this.#onClick(msg.datetime, msg.delta);
+        });
+        event_source.addEventListener("announcement", (event) => {
+            this.#onControlMessage(JSON.parse(event.data));
+        });
+        event_source.addEventListener("user_count", (event) => {
+            this.dispatchEvent(
+                new CustomEvent("updateusercount", {
+                    detail: Number(event.data),
+                }),
+            );
+        });
+        // The browser reconnects on its own, and "open" is dispatched again
+        // once it does.
+        event_source.addEventListener("error", () => {
+            this.#stopTimeSync();
+            this.dispatchEvent(new CustomEvent("close"));
+        });
+    }
+
     /** @param {Event} _event */
     #onOpen(_event) {
         this.dispatchEvent(new CustomEvent("open"));
         this.#reconnect_retries = 0;
+        this.#has_opened = true;
+
+        this.#stopTimeSync();
+        this.#syncTime();
+        this.#time_sync_interval_id = setInterval(
+            this.#syncTime.bind(this),
+            TIME_SYNC_INTERVAL_SECONDS * 1000,
+        );
+    }
+
+    #stopTimeSync() {
+        if (this.#time_sync_interval_id !== null) {
+            clearInterval(this.#time_sync_interval_id);
+            this.#time_sync_interval_id = null;
+        }
+    }
+
+    /** Asks the server for its current time, to measure `clock_offset`. */
+    #syncTime() {
+        if (this.#use_event_source) {
+            fetch(`/api/time?client_time=${Date.now()}`)
+                .then((response) => response.json())
+                .then((msg) =>
+                    this.#onControlMessage({ type: "server_time", ...msg }),
+                )
+                .catch((err) => console.warn("Failed to sync time", err));
+            return;
+        }
+        this.#websocket?.send(
+            JSON.stringify({
+                type: "time_sync",
+                client_time: Date.now(),
+                // Reported back so that the server can keep track of how
+                // skewed clients' clocks are.
+                skew: this.clock_offset === null ? null : -this.clock_offset,
+            }),
+        );
     }
 
     /** @param {MessageEvent<any>} event */
     #onMessage(event) {
-        const msg = new DataView(event.data).getBigInt64(0, false);
+        // Control messages are sent as JSON text, while datetime & user count
+        // updates are sent as binary.
+        if (typeof event.data === "string") {
+            this.#onControlMessage(JSON.parse(event.data));
+            return;
+        }
+
+        const view = new DataView(event.data);
+        // Clicks are followed by how far they moved the datetime.
+        if (view.byteLength >= 16) {
+            this.#onClick(
+                Number(view.getBigInt64(0, false)),
+                Number(view.getBigInt64(8, false)),
+            );
+            return;
+        }
+
+        const msg = view.getBigInt64(0, false);
 
         if (msg > Number.MAX_SAFE_INTEGER) {
             throw new Error(
@@ -69,14 +192,70 @@ export class CustomWebSocket extends EventTarget {
         } else {
             this.dispatchEvent(
                 new CustomEvent("updatedatetime", {
-                    detail: new Date(msg_as_number * 1000),
+                    // Unix timestamp in milliseconds
+                    detail: new Date(msg_as_number),
                 }),
             );
         }
     }
 
-    /** @param {CloseEvent} _event */
-    #onClose(_event) {
+    /** Dispatches the new datetime, then a "click" event with how far it
+     * moved, which is negative if the click subtracted time.
+     * @param {number} datetime Unix timestamp in milliseconds
+     * @param {number} delta milliseconds */
+    #onClick(datetime, delta) {
+        this.dispatchEvent(
+            new CustomEvent("updatedatetime", { detail: new Date(datetime) }),
+        );
+        this.dispatchEvent(new CustomEvent("click", { detail: delta }));
+    }
+
+    /** @param {{ type: string, [key: string]: any }} msg */
+    #onControlMessage(msg) {
+        switch (msg.type) {
+            case "restarting":
+                this.dispatchEvent(new CustomEvent("restarting"));
+                break;
+            case "server_time": {
+                // Assume the message took as long to reach the server as it
+                // took to come back.
+                const now = Date.now();
+                const round_trip = now - msg.client_time;
+                this.clock_offset = Math.round(
+                    msg.server_time + round_trip / 2 - now,
+                );
+                this.dispatchEvent(
+                    new CustomEvent("timesync", { detail: this.clock_offset }),
+                );
+                break;
+            }
+            case "event_started":
+                this.dispatchEvent(
+                    new CustomEvent("eventstarted", {
+                        detail: {
+                            name: msg.name,
+                            // Unix timestamp in milliseconds
+                            end: new Date(msg.end),
+                            multiplier: msg.multiplier,
+                            // How far clicks push the datetime instead of
+                            // the page's usual distribution, if replaced
+                            increment: msg.increment ?? null,
+                        },
+                    }),
+                );
+                break;
+            case "event_ended":
+                this.dispatchEvent(
+                    new CustomEvent("eventended", { detail: msg.name }),
+                );
+                break;
+            default:
+                console.warn(`Unknown WebSocket message type: ${msg.type}`);
+        }
+    }
+
+    /** @param {CloseEvent} event */
+    #onClose(event) {
         if (this.#websocket === null) {
             throw new Error("Tried closing null websocket");
         }
@@ -85,14 +264,39 @@ export class CustomWebSocket extends EventTarget {
         this.#websocket.removeEventListener("close", this.#onClose);
         this.#websocket.removeEventListener("error", this.#onError);
 
+        this.#stopTimeSync();
+
         this.dispatchEvent(new CustomEvent("close"));
+
+        // 1012 (Service Restart): the server is restarting, so reconnect once
+        // it's likely to be back up.
+        if (event.code === 1012 && this.#reconnect_timeout.finished) {
+            console.log(
+                `Server restarting. Reconnecting in ${RESTART_RECONNECT_SECONDS} seconds.`,
+            );
+            this.#reconnect_timeout.setTimeout(RESTART_RECONNECT_SECONDS * 1000);
+            this.#reconnect_timeout.start();
+        }
     }
 
     // TODO: Add popup notif to inform that websocket had error
     /** @param {Event} _event */
     #onError(_event) {
         this.tryDisconnect();
-        const reconnect_seconds = Math.round(Math.min(120, Math.pow(++this.#reconnect_retries, 1.5)));
+        this.#reconnect_retries++;
+        if (
+            !this.#has_opened &&
+            this.#reconnect_retries >= ATTEMPTS_BEFORE_FALLBACK
+        ) {
+            console.log(
+                "Couldn't connect to websocket. Falling back to server-sent events.",
+            );
+            this.#use_event_source = true;
+            this.#reconnect_retries = 0;
+            this.#connectEventSource();
+            return;
+        }
+        const reconnect_seconds = Math.round(Math.min(120, Math.pow(this.#reconnect_retries, 1.5)));
         if (this.#reconnect_timeout.finished) {
             console.log(
                 `Error connecting to websocket. Reconnecting in ${reconnect_seconds} seconds.`,
@@ -103,6 +307,12 @@ export class CustomWebSocket extends EventTarget {
     }
 
     tryConnect() {
+        if (this.#use_event_source) {
+            if (this.#event_source === null) {
+                this.#connectEventSource();
+            }
+            return;
+        }
         if (
             this.#websocket === null ||
             (this.state() !== null && this.state() !== WebSocket.CLOSED)
@@ -113,6 +323,15 @@ export class CustomWebSocket extends EventTarget {
     }
 
     tryDisconnect() {
+        if (this.#use_event_source) {
+            if (this.#event_source !== null) {
+                this.#event_source.close();
+                this.#event_source = null;
+                this.#stopTimeSync();
+                this.dispatchEvent(new CustomEvent("close"));
+            }
+            return;
+        }
         if (
             this.#websocket === null ||
             (this.state() !== null && this.state() !== WebSocket.OPEN)
@@ -139,11 +358,29 @@ export class CustomWebSocket extends EventTarget {
     }
 
     incrementDatetime() {
+        if (this.#use_event_source) {
+            fetch(`/api/${this.page_name}/click`, { method: "POST" }).catch(
+                (err) => console.warn("Failed to click", err),
+            );
+            return;
+        }
         this.#websocket?.send(new Int8Array(0));
     }
 
-    /** @returns {number | null} */
+    /** State of the connection, as a `WebSocket` ready state even when using
+     * server-sent events.
+     * @returns {number | null} */
     state() {
+        if (this.#use_event_source) {
+            switch (this.#event_source?.readyState) {
+                case EventSource.OPEN:
+                    return WebSocket.OPEN;
+                case EventSource.CONNECTING:
+                    return WebSocket.CONNECTING;
+                default:
+                    return WebSocket.CLOSED;
+            }
+        }
         return this.#websocket?.readyState || null;
     }
 }
@@ -44,7 +44,7 @@ function checkBrowserSupportsInactiveTabTimeout() {
 }
 
 let is_document_visible = false;
-const websocket = new CustomWebSocket("battlebit/websocket");
+const websocket = new CustomWebSocket("battlebit");
 
 function executeOnHashUrl() {
     if (window.location.hash === "#info" || window.location.hash === "#what") {
@@ -64,7 +64,7 @@ document.addEventListener("DOMContentLoaded", (_event) => {
     window.addEventListener("popstate", executeOnHashUrl);
 
     const datetime_elem = unwrapSome(document.getElementById("datetime"));
-    const datetime = new Date(Number(datetime_elem.textContent) * 1000);
+    const datetime = new Date(Number(datetime_elem.textContent));
 
     const datetime_display = new DatetimeDisplay(datetime);
     const countdown_display = new CountdownDisplay(datetime);
@@ -72,6 +72,33 @@ document.addEventListener("DOMContentLoaded", (_event) => {
     const user_statistic = new UserStatistic();
 
     const user_count_elem = unwrapSome(document.getElementById("user-count"));
+    const countdown_elem = unwrapSome(document.getElementById("countdown"));
+    const timed_events_elem = unwrapSome(
+        document.getElementById("timed-events"),
+    );
+    /** Timed events that are on, by name.
+     * @type {Map<string, { end: Date, multiplier: number, increment: object | null }>} */
+    const timed_events = new Map();
+
+    function updateTimedEvents() {
+        timed_events_elem.textContent = Array.from(
+            timed_events,
+            ([name, event]) => {
+                const changes = [];
+                if (event.multiplier !== 1) {
+                    changes.push(`×${event.multiplier}`);
+                }
+                if (event.increment !== null) {
+                    changes.push("different click times");
+                }
+                const until = `until ${event.end.toLocaleString()}`;
+                return changes.length === 0
+                    ? `${name} ${until}`
+                    : `${name}: ${changes.join(", ")} ${until}`;
+            },
+        ).join(" · ");
+        timed_events_elem.classList.toggle("hidden", timed_events.size === 0);
+    }
 
     datetime_display.init();
     countdown_display.start();
@@ -94,6 +121,21 @@ document.addEventListener("DOMContentLoaded", (_event) => {
     websocket.addEventListener("close", () => {
         refresh_button.disable();
         user_count_elem.textContent = "-";
+        // Events that are still on are announced again when reconnecting.
+        timed_events.clear();
+        updateTimedEvents();
+    });
+
+    websocket.addEventListener("eventstarted", (event) => {
+        const { name, end, multiplier, increment } =
+            /** @type {CustomEvent} */ (event).detail;
+        timed_events.set(name, { end, multiplier, increment });
+        updateTimedEvents();
+    });
+
+    websocket.addEventListener("eventended", (event) => {
+        timed_events.delete(/** @type {CustomEvent} */ (event).detail);
+        updateTimedEvents();
     });
 
     websocket.addEventListener("updatedatetime", (event) => {
@@ -104,13 +146,30 @@ document.addEventListener("DOMContentLoaded", (_event) => {
         countdown_display.updateDatetimeTarget(datetime);
     });
 
+    websocket.addEventListener("click", (event) => {
+        const delta = /** @type {CustomEvent} */ (event).detail;
+        if (delta === 0) {
+            return;
+        }
+        // Restart the animation, in case the previous click's is still
+        // running.
+        countdown_elem.classList.remove("time-added", "time-subtracted");
+        void countdown_elem.offsetWidth;
+        countdown_elem.classList.add(
+            delta > 0 ? "time-added" : "time-subtracted",
+        );
+    });
+
+    websocket.addEventListener("timesync", (event) => {
+        const clock_offset = /** @type {CustomEvent} */ (event).detail;
+        countdown_display.setClockOffset(clock_offset);
+    });
+
     websocket.addEventListener("updateusercount", (event) => {
         const user_count = /** @type {CustomEvent} */ (event).detail;
         user_count_elem.textContent = String(user_count);
     });
 
-    const countdown_elem = unwrapSome(document.getElementById("countdown"));
-
     countdown_elem.addEventListener("click", () => {
         countdown_display.cycleState();
     });
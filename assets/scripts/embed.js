@@ -0,0 +1,70 @@
+// @ts-check
+"use strict";
+
+import { getDuration } from "../modules/datetime/duration";
+import { CustomWebSocket } from "../modules/websocket";
+
+import { unwrapSome } from "../modules/utils/assert";
+
+/** @param {number} value */
+function pad(value) {
+    return String(value).padStart(2, "0");
+}
+
+// main
+document.addEventListener("DOMContentLoaded", (_event) => {
+    const countdown_elem = unwrapSome(document.getElementById("countdown"));
+    const refresh_button = document.getElementById("refresh");
+
+    const page_name = unwrapSome(countdown_elem.dataset.page);
+    let datetime = new Date(Number(countdown_elem.dataset.datetime));
+    /** Milliseconds to add to the local clock to get the server's time. */
+    let clock_offset = 0;
+
+    const websocket = new CustomWebSocket(page_name);
+
+    function update() {
+        const now = new Date(Date.now() + clock_offset);
+        if (now >= datetime) {
+            countdown_elem.textContent = "0:00:00:00";
+            return;
+        }
+        const duration = getDuration(now, datetime);
+        countdown_elem.textContent = `${duration.total_days}:${pad(duration.hours)}:${pad(duration.minutes)}:${pad(duration.seconds)}`;
+    }
+
+    update();
+    setInterval(update, 200);
+
+    websocket.addEventListener("updatedatetime", (event) => {
+        datetime = /** @type {Date} */ (
+            /** @type {CustomEvent} */ (event).detail
+        );
+        update();
+    });
+
+    websocket.addEventListener("timesync", (event) => {
+        clock_offset = /** @type {CustomEvent} */ (event).detail;
+        update();
+    });
+
+    if (refresh_button === null) {
+        return;
+    }
+
+    refresh_button.addEventListener("click", () => {
+        if (websocket.state() === WebSocket.OPEN) {
+            websocket.incrementDatetime();
+        } else if (websocket.state() === WebSocket.CLOSED) {
+            websocket.tryConnect();
+        }
+    });
+
+    websocket.addEventListener("open", () => {
+        refresh_button.removeAttribute("disabled");
+    });
+
+    websocket.addEventListener("close", () => {
+        refresh_button.setAttribute("disabled", "");
+    });
+});
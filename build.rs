@@ -12,7 +12,7 @@ fn main() {
     println!("cargo::rerun-if-changed=build.rs");
     println!("cargo::rerun-if-changed={}", output_path);
 
-    let _ = vec!["assets", "templates"]
+    let _ = ["assets", "templates", "migrations"]
         .iter()
         .map(|p| println!("cargo::rerun-if-changed={}", p))
         .collect::<Vec<_>>();
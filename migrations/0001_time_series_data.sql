@@ -0,0 +1,18 @@
+-- `IF NOT EXISTS` so that databases created before migrations were introduced (when the table was
+-- created on startup) can be migrated without manual intervention.
+CREATE TABLE IF NOT EXISTS time_series_data (
+  timestamp      TIMESTAMP WITHOUT TIME ZONE    NOT NULL,
+  page_name      TEXT                           NOT NULL,
+  datetime       TIMESTAMP WITHOUT TIME ZONE    NOT NULL,
+  click_count    BIGINT                         NOT NULL,
+  user_count     INTEGER                        NOT NULL
+)
+WITH (
+  tsdb.hypertable,
+  tsdb.partition_column = 'timestamp',
+  tsdb.segmentby = 'page_name',
+  tsdb.chunk_interval = '1d',
+  tsdb.orderby = 'timestamp DESC'
+);
+
+CALL add_columnstore_policy('time_series_data', after => INTERVAL '1d', if_not_exists => TRUE);
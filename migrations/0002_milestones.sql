@@ -0,0 +1,15 @@
+-- Notable events of each page, shown in its feed.
+CREATE TABLE milestones (
+  id             BIGINT GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+  -- When the milestone happened.
+  timestamp      TIMESTAMP WITHOUT TIME ZONE    NOT NULL,
+  page_name      TEXT                           NOT NULL,
+  -- `click_count`, `new_year` or `resolved`.
+  kind           TEXT                           NOT NULL,
+  -- The click count or year reached, depending on the kind.
+  value          BIGINT,
+  -- The page's datetime at the time.
+  datetime       TIMESTAMP WITHOUT TIME ZONE    NOT NULL
+);
+
+CREATE INDEX milestones_page_name_timestamp_idx ON milestones (page_name, timestamp DESC);
@@ -0,0 +1,16 @@
+-- Webhook deliveries that failed every attempt, kept so they can be retried with the
+-- `retry-webhooks` subcommand.
+CREATE TABLE webhook_dead_letters (
+  id             BIGINT GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+  -- When the last attempt failed.
+  timestamp      TIMESTAMP WITHOUT TIME ZONE    NOT NULL,
+  url            TEXT                           NOT NULL,
+  -- `milestone` or `resolved`.
+  event          TEXT                           NOT NULL,
+  -- Sent in the `X-Webhook-Delivery` header, so receivers can tell retries apart from new events.
+  delivery_id    TEXT                           NOT NULL,
+  -- JSON body of the request.
+  payload        TEXT                           NOT NULL,
+  attempts       INTEGER                        NOT NULL,
+  last_error     TEXT                           NOT NULL
+);
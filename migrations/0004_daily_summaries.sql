@@ -0,0 +1,11 @@
+-- Stats of each page over each day (in UTC), computed from `time_series_data` after the day ends.
+CREATE TABLE daily_summaries (
+  page_name          TEXT                NOT NULL,
+  date               DATE                NOT NULL,
+  clicks_added       BIGINT              NOT NULL,
+  -- How far the clicks pushed the datetime.
+  seconds_added      DOUBLE PRECISION    NOT NULL,
+  -- Most users connected at once.
+  peak_user_count    INTEGER             NOT NULL,
+  PRIMARY KEY (page_name, date)
+);
@@ -0,0 +1,163 @@
+use serde::Deserialize;
+
+use crate::feed::escape_xml;
+
+/// Colors that can be given by name, like on shields.io.
+const NAMED_COLORS: [(&str, &str); 9] = [
+    ("brightgreen", "#4c1"),
+    ("green", "#97ca00"),
+    ("yellow", "#dfb317"),
+    ("orange", "#fe7d37"),
+    ("red", "#e05d44"),
+    ("blue", "#007ec6"),
+    ("grey", "#555"),
+    ("lightgrey", "#9f9f9f"),
+    ("purple", "#9f5ec6"),
+];
+const LABEL_COLOR: &str = "#555";
+
+/// Looks of a badge, named after the shields.io styles they imitate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BadgeStyle {
+    /// Rounded corners with a gradient.
+    #[default]
+    Flat,
+    /// Square corners without a gradient.
+    FlatSquare,
+    /// Taller, with uppercase bold text.
+    ForTheBadge,
+}
+
+impl BadgeStyle {
+    fn height(self) -> u32 {
+        match self {
+            BadgeStyle::Flat | BadgeStyle::FlatSquare => 20,
+            BadgeStyle::ForTheBadge => 28,
+        }
+    }
+
+    /// Horizontal padding on each side of the text.
+    fn padding(self) -> u32 {
+        match self {
+            BadgeStyle::Flat | BadgeStyle::FlatSquare => 6,
+            BadgeStyle::ForTheBadge => 12,
+        }
+    }
+}
+
+/// Parses a color given by name (e.g. `blue`) or as a hex code without the `#` (e.g. `ff69b4`),
+/// returning it as a CSS color. Anything else is rejected, since it ends up in the SVG.
+pub fn parse_color(color: &str) -> Option<String> {
+    if let Some((_, hex)) = NAMED_COLORS.iter().find(|(name, _)| *name == color) {
+        return Some(hex.to_string());
+    }
+
+    let is_hex =
+        (color.len() == 3 || color.len() == 6) && color.chars().all(|c| c.is_ascii_hexdigit());
+    is_hex.then(|| format!("#{}", color))
+}
+
+/// Rough width of text in pixels. The badge is rendered by the viewer with whatever font it has,
+/// so the exact font metrics aren't known anyway.
+fn text_width(text: &str, style: BadgeStyle) -> u32 {
+    let char_width = match style {
+        BadgeStyle::Flat | BadgeStyle::FlatSquare => 7,
+        // Bold uppercase letters with letter spacing.
+        BadgeStyle::ForTheBadge => 9,
+    };
+    text.chars().count() as u32 * char_width
+}
+
+/// Renders a badge with the label on a grey background on the left, and the message on a
+/// background of the given color on the right. `color` must come from `parse_color`.
+pub fn render_badge(label: &str, message: &str, color: &str, style: BadgeStyle) -> String {
+    let (label, message) = match style {
+        BadgeStyle::ForTheBadge => (label.to_uppercase(), message.to_uppercase()),
+        BadgeStyle::Flat | BadgeStyle::FlatSquare => (label.to_string(), message.to_string()),
+    };
+    let height = style.height();
+    let padding = style.padding();
+    let label_width = text_width(&label, style) + padding * 2;
+    let message_width = text_width(&message, style) + padding * 2;
+    let width = label_width + message_width;
+    let text_y = height / 2 + 4;
+
+    let (radius, gradient, font) = match style {
+        BadgeStyle::Flat => (
+            3,
+            "<linearGradient id=\"s\" x2=\"0\" y2=\"100%\"><stop offset=\"0\" stop-color=\"#bbb\" \
+            stop-opacity=\".1\"/><stop offset=\"1\" stop-opacity=\".1\"/></linearGradient>",
+            "font-size=\"11\"",
+        ),
+        BadgeStyle::FlatSquare => (0, "", "font-size=\"11\""),
+        BadgeStyle::ForTheBadge => (
+            0,
+            "",
+            "font-size=\"10\" font-weight=\"bold\" letter-spacing=\"1\"",
+        ),
+    };
+    let gradient_rect = if gradient.is_empty() {
+        String::new()
+    } else {
+        format!(
+            "<rect width=\"{}\" height=\"{}\" fill=\"url(#s)\"/>",
+            width, height
+        )
+    };
+
+    let label = escape_xml(&label);
+    let message = escape_xml(&message);
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" \
+        role=\"img\" aria-label=\"{label}: {message}\">\
+        <title>{label}: {message}</title>\
+        {gradient}\
+        <clipPath id=\"r\"><rect width=\"{width}\" height=\"{height}\" rx=\"{radius}\" fill=\"#fff\"/></clipPath>\
+        <g clip-path=\"url(#r)\">\
+        <rect width=\"{label_width}\" height=\"{height}\" fill=\"{LABEL_COLOR}\"/>\
+        <rect x=\"{label_width}\" width=\"{message_width}\" height=\"{height}\" fill=\"{color}\"/>\
+        {gradient_rect}\
+        </g>\
+        <g fill=\"#fff\" text-anchor=\"middle\" font-family=\"Verdana,Geneva,DejaVu Sans,sans-serif\" {font}>\
+        <text x=\"{label_x}\" y=\"{text_y}\">{label}</text>\
+        <text x=\"{message_x}\" y=\"{text_y}\">{message}</text>\
+        </g>\
+        </svg>",
+        label_x = label_width / 2,
+        message_x = label_width + message_width / 2,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::badge::{BadgeStyle, parse_color, render_badge};
+
+    #[test]
+    fn colors() {
+        assert_eq!(parse_color("blue").as_deref(), Some("#007ec6"));
+        assert_eq!(parse_color("ff69b4").as_deref(), Some("#ff69b4"));
+        assert_eq!(parse_color("f00").as_deref(), Some("#f00"));
+        assert_eq!(parse_color("#f00"), None);
+        assert_eq!(parse_color("red\"/><script>"), None);
+        assert_eq!(parse_color("ggg"), None);
+    }
+
+    #[test]
+    fn render() {
+        let svg = render_badge(
+            "BattleBit <updates> in",
+            "812d 3h 0m 12s",
+            "#007ec6",
+            BadgeStyle::Flat,
+        );
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+        assert!(svg.contains(">BattleBit &lt;updates&gt; in</text>"));
+        assert!(svg.contains(">812d 3h 0m 12s</text>"));
+        assert!(svg.contains("fill=\"#007ec6\""));
+
+        let svg = render_badge("a", "b", "#007ec6", BadgeStyle::ForTheBadge);
+        assert!(svg.contains(">A</text>"));
+        assert!(svg.contains("height=\"28\""));
+    }
+}
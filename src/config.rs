@@ -0,0 +1,161 @@
+use std::fs;
+use std::io::ErrorKind;
+
+use serde::Deserialize;
+
+use crate::webhooks::WebhookEventKind;
+
+/// Settings loaded from the config file. Every field has a default, so the file (or any part of
+/// it) can be omitted.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct Config {
+    /// URL the site is served at, without a trailing slash (e.g. `"https://example.com"`). Used
+    /// for links that have to be absolute, like Open Graph images, which are left out of pages if
+    /// not set.
+    pub public_url: Option<String>,
+    /// Token for the admin API (e.g. scheduling timed events), sent as
+    /// `Authorization: Bearer <token>`. The admin API is disabled if not set.
+    pub admin_token: Option<String>,
+    pub log: LogConfig,
+    pub time_series: TimeSeriesConfig,
+    pub rate_limit: RateLimitConfig,
+    pub webhooks: Vec<WebhookConfig>,
+}
+
+impl Config {
+    /// Loads the config from `path`, falling back to the defaults if the file doesn't exist.
+    pub fn load(path: impl AsRef<std::path::Path>) -> Self {
+        let config: Self = match fs::read_to_string(path) {
+            Ok(file_contents) => serde_json::from_str(&file_contents).unwrap(),
+            Err(err) if err.kind() == ErrorKind::NotFound => Self::default(),
+            Err(err) => panic!("failed to read config file: {}", err),
+        };
+        if let Err(err) = config.rate_limit.validate() {
+            panic!("invalid `rate_limit` config: {}", err);
+        }
+        config
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct LogConfig {
+    /// Filter directives in the same syntax as `RUST_LOG` (e.g. `"info"` or
+    /// `"update_countdown=debug,tower_http=info"`). The `RUST_LOG` environment variable takes
+    /// precedence over this if set.
+    pub level: String,
+    pub format: LogFormat,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            level: "info".to_string(),
+            format: LogFormat::default(),
+        }
+    }
+}
+
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human readable, multi-line output.
+    #[default]
+    Pretty,
+    /// One JSON object per line, for log aggregators.
+    Json,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct TimeSeriesConfig {
+    /// How long to keep rows in `time_series_data`, as a Postgres interval (e.g. `"30 days"`).
+    /// Rows are kept forever if not set. Should be longer than the refresh window of the daily
+    /// aggregate (3 days), otherwise rows are dropped before they are rolled up.
+    pub raw_retention: Option<String>,
+    pub aggregates: AggregatesConfig,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct AggregatesConfig {
+    pub minute: AggregateConfig,
+    pub hour: AggregateConfig,
+    pub day: AggregateConfig,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct AggregateConfig {
+    /// Disabling an aggregate drops its materialized view on the next startup.
+    pub enabled: bool,
+    /// How long to keep buckets of this aggregate, as a Postgres interval. Kept forever if not
+    /// set.
+    pub retention: Option<String>,
+}
+
+impl Default for AggregateConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            retention: None,
+        }
+    }
+}
+
+/// Limits how fast each client can click, over websockets and HTTP combined.
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    pub clicks_per_second: f64,
+    /// How many clicks can be made at once before being limited to `clicks_per_second`.
+    pub burst: u32,
+    /// Whether to take the client's IP from the last address in the `X-Forwarded-For` header,
+    /// which should only be enabled behind a single reverse proxy that sets it. Otherwise, every
+    /// client of the proxy shares the same limit.
+    pub trust_forwarded_for: bool,
+}
+
+/// Lowest `clicks_per_second`, i.e. one click every ~17 minutes. The rate limiter divides by it, so
+/// it has to be bounded to keep the resulting durations from overflowing.
+const MIN_CLICKS_PER_SECOND: f64 = 0.001;
+
+impl RateLimitConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        let rate = self.clicks_per_second;
+        if !(rate.is_finite() && rate >= MIN_CLICKS_PER_SECOND) {
+            return Err(format!(
+                "`clicks_per_second` must be a number of at least {}, got {}",
+                MIN_CLICKS_PER_SECOND, rate
+            ));
+        }
+        // Otherwise no click would ever be allowed.
+        if self.burst == 0 {
+            return Err("`burst` must be at least 1".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            clicks_per_second: 20.0,
+            burst: 40,
+            trust_forwarded_for: false,
+        }
+    }
+}
+
+/// Endpoint that events are sent to as JSON `POST` requests.
+#[derive(Deserialize, Clone)]
+pub struct WebhookConfig {
+    pub url: String,
+    /// Events to send (`milestone`, `resolved`, `daily_summary`). Every event is sent if empty.
+    #[serde(default)]
+    pub events: Vec<WebhookEventKind>,
+    /// Secret to sign the body with, so that the receiver can check it came from here.
+    #[serde(default)]
+    pub secret: Option<String>,
+}
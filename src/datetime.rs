@@ -1,15 +1,40 @@
-use chrono::{Datelike, NaiveDateTime, Timelike};
+use chrono::{Datelike, Months, NaiveDateTime, Timelike};
+use serde::{Deserialize, Serialize};
 
-/// Calculates the duration from the datetime to now, returning the difference in seconds, minutes,
-/// hours, and days.
+/// A duration broken down into units. Years, months, and weeks are only used by
+/// `calendar_duration_parts`, since their length depends on where the duration starts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DurationParts {
+    pub years: i32,
+    pub months: i32,
+    pub weeks: i32,
+    pub days: i32,
+    pub hours: i32,
+    pub minutes: i32,
+    pub seconds: i32,
+    pub milliseconds: i32,
+}
+
+/// Calculates the duration between two datetimes, returning the difference in seconds, minutes,
+/// hours, and days. The order doesn't matter, since only the magnitude is returned.
 pub fn datetime_difference(from: NaiveDateTime, to: NaiveDateTime) -> String {
-    let milliseconds = (to.nanosecond() as i32 - from.nanosecond() as i32) / 1_000_000;
+    format_duration(duration_parts(from, to), DurationStyle::Compact, Locale::En)
+}
+
+/// Calculates the duration from `from` to `to`, broken down into days, hours, minutes, seconds,
+/// and milliseconds. If `to` is before `from`, the duration from `to` to `from` is returned
+/// instead; use `SignedDuration` to tell them apart.
+pub fn duration_parts(from: NaiveDateTime, to: NaiveDateTime) -> DurationParts {
+    let (from, to) = if from <= to { (from, to) } else { (to, from) };
+    let mut milliseconds =
+        (to.nanosecond() / 1_000_000) as i32 - (from.nanosecond() / 1_000_000) as i32;
     let mut seconds = to.second() as i32 - from.second() as i32;
     let mut minutes = to.minute() as i32 - from.minute() as i32;
     let mut hours = to.hour() as i32 - from.hour() as i32;
     let mut days = to.num_days_from_ce() - from.num_days_from_ce();
 
     if milliseconds < 0 {
+        milliseconds += 1000;
         seconds -= 1;
     }
 
@@ -28,39 +53,426 @@ pub fn datetime_difference(from: NaiveDateTime, to: NaiveDateTime) -> String {
         days -= 1;
     }
 
-    assert!(days >= 0);
+    DurationParts {
+        days,
+        hours,
+        minutes,
+        seconds,
+        milliseconds,
+        ..Default::default()
+    }
+}
+
+/// Calculates the duration from `from` to `to`, broken down into years, months, weeks, days,
+/// hours, minutes, and seconds, so that adding the years & months to `from` as calendar months,
+/// then the rest as a fixed duration, gives `to` (down to the millisecond).
+///
+/// Adding months to a day that doesn't exist in the resulting month clamps it to the end of that
+/// month (e.g. Jan 31 + 1 month is Feb 28), like `NaiveDateTime::checked_add_months`. So from Jan
+/// 31 to Mar 1 is 1 month and 1 day (or 2 days in leap years), not 1 month and -2 days.
+///
+/// If `to` is before `from`, the arguments are swapped, so a datetime that has already passed gets
+/// how long ago it was.
+pub fn calendar_duration_parts(from: NaiveDateTime, to: NaiveDateTime) -> DurationParts {
+    let (from, to) = if from <= to { (from, to) } else { (to, from) };
+
+    let mut total_months = (to.year() - from.year()) * 12 + to.month() as i32 - from.month() as i32;
+    let add_months = |months: i32| from.checked_add_months(Months::new(months as u32)).unwrap();
+    // Borrow a month if the day or time of `to` is before that of `from`.
+    let mut month_aligned = add_months(total_months.max(0));
+    while total_months > 0 && month_aligned > to {
+        total_months -= 1;
+        month_aligned = add_months(total_months);
+    }
+
+    let rest = duration_parts(month_aligned, to);
+
+    DurationParts {
+        years: total_months / 12,
+        months: total_months % 12,
+        weeks: rest.days / 7,
+        days: rest.days % 7,
+        ..rest
+    }
+}
+
+/// Duration from now until a target datetime, which may have already passed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SignedDuration {
+    /// Whether the target is in the past, in which case `parts` is how long ago it was.
+    pub overdue: bool,
+    pub parts: DurationParts,
+}
+
+impl SignedDuration {
+    /// Drops the milliseconds if they aren't wanted, rounding towards zero.
+    pub fn with_precision(self, precision: Precision) -> Self {
+        match precision {
+            Precision::Seconds => Self {
+                parts: DurationParts {
+                    milliseconds: 0,
+                    ..self.parts
+                },
+                ..self
+            },
+            Precision::Milliseconds => self,
+        }
+    }
+
+    /// Calendar-aware duration from `now` until `target`, see `calendar_duration_parts`.
+    pub fn until(now: NaiveDateTime, target: NaiveDateTime) -> Self {
+        if target >= now {
+            Self {
+                overdue: false,
+                parts: calendar_duration_parts(now, target),
+            }
+        } else {
+            Self {
+                overdue: true,
+                parts: calendar_duration_parts(target, now),
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DurationStyle {
+    /// Abbreviated units, starting from the greatest non-zero unit, e.g. `6858d 10h 54m 34s`.
+    #[default]
+    Compact,
+    /// Full unit names, skipping units that are zero, e.g. `6858 days, 10 hours, 54 minutes, 34
+    /// seconds`.
+    Long,
+    /// ISO 8601 duration, e.g. `P6858DT10H54M34S`. The same for every locale.
+    Iso8601,
+}
 
-    difference_to_string(seconds, minutes, hours, days)
+/// Smallest unit that durations are formatted with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Precision {
+    #[default]
+    Seconds,
+    /// Seconds are formatted with 3 decimal places if there are any milliseconds, e.g. `34.250s`.
+    Milliseconds,
 }
 
-fn difference_to_string(seconds: i32, minutes: i32, hours: i32, days: i32) -> String {
-    let mut time_units = Vec::with_capacity(4);
-    let mut greatest_unit_found = false;
+/// Languages that durations can be formatted in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Locale {
+    #[default]
+    En,
+    De,
+    Es,
+    Fr,
+    Pt,
+}
 
-    if days != 0 {
-        greatest_unit_found = true;
-        time_units.push(format!("{}d", days));
+/// Name of a time unit in a locale.
+struct UnitName {
+    short: &'static str,
+    singular: &'static str,
+    plural: &'static str,
+}
+
+const fn unit(short: &'static str, singular: &'static str, plural: &'static str) -> UnitName {
+    UnitName {
+        short,
+        singular,
+        plural,
     }
+}
+
+/// Year, month, week, day, hour, minute, and second names of each locale.
+const EN_UNITS: [UnitName; 7] = [
+    unit("Y", "year", "years"),
+    unit("M", "month", "months"),
+    unit("w", "week", "weeks"),
+    unit("d", "day", "days"),
+    unit("h", "hour", "hours"),
+    unit("m", "minute", "minutes"),
+    unit("s", "second", "seconds"),
+];
+const DE_UNITS: [UnitName; 7] = [
+    unit("J.", "Jahr", "Jahre"),
+    unit("Mon.", "Monat", "Monate"),
+    unit("Wo.", "Woche", "Wochen"),
+    unit("T", "Tag", "Tage"),
+    unit("Std.", "Stunde", "Stunden"),
+    unit("Min.", "Minute", "Minuten"),
+    unit("Sek.", "Sekunde", "Sekunden"),
+];
+const ES_UNITS: [UnitName; 7] = [
+    unit("a", "año", "años"),
+    unit("mes", "mes", "meses"),
+    unit("sem", "semana", "semanas"),
+    unit("d", "día", "días"),
+    unit("h", "hora", "horas"),
+    unit("min", "minuto", "minutos"),
+    unit("s", "segundo", "segundos"),
+];
+const FR_UNITS: [UnitName; 7] = [
+    unit("a", "an", "ans"),
+    unit("mois", "mois", "mois"),
+    unit("sem", "semaine", "semaines"),
+    unit("j", "jour", "jours"),
+    unit("h", "heure", "heures"),
+    unit("min", "minute", "minutes"),
+    unit("s", "seconde", "secondes"),
+];
+const PT_UNITS: [UnitName; 7] = [
+    unit("a", "ano", "anos"),
+    unit("m", "mês", "meses"),
+    unit("sem", "semana", "semanas"),
+    unit("d", "dia", "dias"),
+    unit("h", "hora", "horas"),
+    unit("min", "minuto", "minutos"),
+    unit("s", "segundo", "segundos"),
+];
 
-    if hours != 0 || greatest_unit_found {
-        greatest_unit_found = true;
-        time_units.push(format!("{}h", hours));
+impl Locale {
+    fn from_tag(tag: &str) -> Option<Self> {
+        // Only the primary language subtag matters, e.g. `pt` in `pt-BR`.
+        let language = tag.split('-').next()?.trim().to_ascii_lowercase();
+        match language.as_str() {
+            "en" => Some(Locale::En),
+            "de" => Some(Locale::De),
+            "es" => Some(Locale::Es),
+            "fr" => Some(Locale::Fr),
+            "pt" => Some(Locale::Pt),
+            _ => None,
+        }
     }
 
-    if minutes != 0 || greatest_unit_found {
-        time_units.push(format!("{}m", minutes));
+    /// Picks the supported locale with the highest quality value from an `Accept-Language` header
+    /// value, e.g. `fr-CH, fr;q=0.9, en;q=0.8, *;q=0.5`. Falls back to English.
+    pub fn from_accept_language(header: &str) -> Self {
+        let mut best: Option<(f32, Locale)> = None;
+        for item in header.split(',') {
+            let mut params = item.split(';');
+            let Some(locale) = params.next().and_then(Locale::from_tag) else {
+                continue;
+            };
+            let quality = params
+                .find_map(|param| param.trim().strip_prefix("q="))
+                .and_then(|q| q.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+
+            // Strictly greater, so that earlier items win ties.
+            if quality > 0.0 && best.is_none_or(|(best_quality, _)| quality > best_quality) {
+                best = Some((quality, locale));
+            }
+        }
+
+        best.map(|(_, locale)| locale).unwrap_or_default()
     }
 
-    time_units.push(format!("{}s", seconds));
+    /// Year, month, week, day, hour, minute, and second names.
+    fn units(self) -> &'static [UnitName; 7] {
+        match self {
+            Locale::En => &EN_UNITS,
+            Locale::De => &DE_UNITS,
+            Locale::Es => &ES_UNITS,
+            Locale::Fr => &FR_UNITS,
+            Locale::Pt => &PT_UNITS,
+        }
+    }
 
-    time_units.join(" ")
+    /// Whether abbreviated units are separated from their value by a space.
+    fn spaced_short_units(self) -> bool {
+        self != Locale::En
+    }
+
+    /// Wraps a formatted duration to say that it's overdue by that amount.
+    pub fn overdue(self, duration: &str) -> String {
+        match self {
+            Locale::En => format!("overdue by {}", duration),
+            Locale::De => format!("{} überfällig", duration),
+            Locale::Es => format!("con {} de retraso", duration),
+            Locale::Fr => format!("en retard de {}", duration),
+            Locale::Pt => format!("atrasado há {}", duration),
+        }
+    }
+
+    fn decimal_separator(self) -> char {
+        match self {
+            Locale::En => '.',
+            Locale::De | Locale::Es | Locale::Fr | Locale::Pt => ',',
+        }
+    }
+
+    fn is_singular(self, value: i32) -> bool {
+        match self {
+            // French uses the singular for zero as well.
+            Locale::Fr => value == 0 || value == 1,
+            Locale::En | Locale::De | Locale::Es | Locale::Pt => value == 1,
+        }
+    }
+
+    /// Like `is_singular`, but for seconds that may have decimal places.
+    fn is_singular_seconds(self, parts: DurationParts) -> bool {
+        match self {
+            // French uses the singular for anything less than 2.
+            Locale::Fr => parts.seconds <= 1,
+            Locale::En | Locale::De | Locale::Es | Locale::Pt => {
+                parts.seconds == 1 && parts.milliseconds == 0
+            }
+        }
+    }
+}
+
+/// Formats the seconds of a duration, with the milliseconds as decimal places if there are any.
+fn format_seconds(parts: DurationParts, decimal_separator: char) -> String {
+    if parts.milliseconds == 0 {
+        parts.seconds.to_string()
+    } else {
+        format!(
+            "{}{}{:03}",
+            parts.seconds, decimal_separator, parts.milliseconds
+        )
+    }
+}
+
+pub fn format_duration(parts: DurationParts, style: DurationStyle, locale: Locale) -> String {
+    let values = [
+        parts.years,
+        parts.months,
+        parts.weeks,
+        parts.days,
+        parts.hours,
+        parts.minutes,
+        parts.seconds,
+    ];
+    let units = locale.units();
+    let seconds_unit = values.len() - 1;
+    let seconds = format_seconds(parts, locale.decimal_separator());
+    let has_seconds = parts.seconds != 0 || parts.milliseconds != 0;
+
+    match style {
+        DurationStyle::Compact => {
+            let separator = if locale.spaced_short_units() { " " } else { "" };
+            // Start from the greatest non-zero unit, always including seconds.
+            let first_unit = values[..seconds_unit]
+                .iter()
+                .position(|&value| value != 0)
+                .unwrap_or(seconds_unit);
+
+            values[first_unit..seconds_unit]
+                .iter()
+                .map(|value| value.to_string())
+                .chain([seconds])
+                .zip(&units[first_unit..])
+                .map(|(value, unit)| format!("{}{}{}", value, separator, unit.short))
+                .collect::<Vec<_>>()
+                .join(" ")
+        }
+        DurationStyle::Long => {
+            let mut time_units = values[..seconds_unit]
+                .iter()
+                .zip(units)
+                .filter(|(value, _)| **value != 0)
+                .map(|(&value, unit)| {
+                    let name = if locale.is_singular(value) {
+                        unit.singular
+                    } else {
+                        unit.plural
+                    };
+                    format!("{} {}", value, name)
+                })
+                .collect::<Vec<_>>();
+
+            if has_seconds || time_units.is_empty() {
+                let unit = &units[seconds_unit];
+                let name = if locale.is_singular_seconds(parts) {
+                    unit.singular
+                } else {
+                    unit.plural
+                };
+                time_units.push(format!("{} {}", seconds, name));
+            }
+            time_units.join(", ")
+        }
+        DurationStyle::Iso8601 => {
+            // Weeks can't be combined with other units in ISO 8601, so they are added to days.
+            let date_units = [
+                (parts.years, 'Y'),
+                (parts.months, 'M'),
+                (parts.weeks * 7 + parts.days, 'D'),
+            ];
+            let time_units = [(parts.hours, 'H'), (parts.minutes, 'M')];
+
+            let mut iso = String::from("P");
+            for (value, designator) in date_units {
+                if value != 0 {
+                    iso.push_str(&format!("{}{}", value, designator));
+                }
+            }
+            if has_seconds || time_units.iter().any(|(value, _)| *value != 0) {
+                iso.push('T');
+                for (value, designator) in time_units {
+                    if value != 0 {
+                        iso.push_str(&format!("{}{}", value, designator));
+                    }
+                }
+                if has_seconds {
+                    iso.push_str(&format!("{}S", format_seconds(parts, '.')));
+                }
+            } else if iso.len() == 1 {
+                iso.push_str("T0S");
+            }
+            iso
+        }
+    }
+}
+
+/// Formats a signed duration, saying that it's overdue (e.g. `overdue by 3d 2h 0m 0s`) if the
+/// target has passed. ISO 8601 durations are negated instead (e.g. `-P3DT2H`).
+pub fn format_signed_duration(
+    duration: SignedDuration,
+    style: DurationStyle,
+    locale: Locale,
+) -> String {
+    let formatted = format_duration(duration.parts, style, locale);
+    match (duration.overdue, style) {
+        (false, _) => formatted,
+        (true, DurationStyle::Iso8601) => format!("-{}", formatted),
+        (true, DurationStyle::Compact | DurationStyle::Long) => locale.overdue(&formatted),
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use chrono::{TimeZone, Utc};
+    use chrono::{Months, NaiveDate, TimeDelta, TimeZone, Utc};
+    use proptest::prelude::*;
 
-    use crate::datetime::datetime_difference;
+    use crate::datetime::{
+        DurationParts, DurationStyle, Locale, Precision, SignedDuration, calendar_duration_parts,
+        datetime_difference, duration_parts, format_duration, format_signed_duration,
+    };
+
+    const PARTS: DurationParts = DurationParts {
+        years: 0,
+        months: 0,
+        weeks: 0,
+        days: 6858,
+        hours: 1,
+        minutes: 0,
+        seconds: 34,
+        milliseconds: 0,
+    };
+
+    const CALENDAR_PARTS: DurationParts = DurationParts {
+        years: 18,
+        months: 9,
+        weeks: 1,
+        days: 3,
+        hours: 0,
+        minutes: 0,
+        seconds: 0,
+        milliseconds: 0,
+    };
 
     #[test]
     fn none() {
@@ -131,4 +543,279 @@ mod tests {
         let b = Utc.with_ymd_and_hms(2025, 8, 17, 7, 27, 49).unwrap().naive_utc();
         assert_eq!(datetime_difference(a, b), "6858d 10h 54m 34s");
     }
+
+    #[test]
+    fn reversed() {
+        let a = Utc.with_ymd_and_hms(2006, 11, 6, 20, 33, 15).unwrap().naive_utc();
+        let b = Utc.with_ymd_and_hms(2025, 8, 17, 7, 27, 49).unwrap().naive_utc();
+        assert_eq!(datetime_difference(b, a), "6858d 10h 54m 34s");
+        assert_eq!(duration_parts(b, a), duration_parts(a, b));
+    }
+
+    #[test]
+    fn compact_locales() {
+        let cases = [
+            (Locale::En, "6858d 1h 0m 34s"),
+            (Locale::De, "6858 T 1 Std. 0 Min. 34 Sek."),
+            (Locale::Es, "6858 d 1 h 0 min 34 s"),
+            (Locale::Fr, "6858 j 1 h 0 min 34 s"),
+            (Locale::Pt, "6858 d 1 h 0 min 34 s"),
+        ];
+        for (locale, expected) in cases {
+            assert_eq!(format_duration(PARTS, DurationStyle::Compact, locale), expected);
+        }
+    }
+
+    #[test]
+    fn long_locales() {
+        let cases = [
+            (Locale::En, "6858 days, 1 hour, 34 seconds"),
+            (Locale::De, "6858 Tage, 1 Stunde, 34 Sekunden"),
+            (Locale::Es, "6858 días, 1 hora, 34 segundos"),
+            (Locale::Fr, "6858 jours, 1 heure, 34 secondes"),
+            (Locale::Pt, "6858 dias, 1 hora, 34 segundos"),
+        ];
+        for (locale, expected) in cases {
+            assert_eq!(format_duration(PARTS, DurationStyle::Long, locale), expected);
+        }
+    }
+
+    #[test]
+    fn long_zero() {
+        let zero = DurationParts::default();
+        assert_eq!(format_duration(zero, DurationStyle::Long, Locale::En), "0 seconds");
+        assert_eq!(format_duration(zero, DurationStyle::Long, Locale::Fr), "0 seconde");
+    }
+
+    #[test]
+    fn iso8601() {
+        let cases = [
+            (PARTS, "P6858DT1H34S"),
+            (DurationParts::default(), "PT0S"),
+            (DurationParts { days: 11, ..Default::default() }, "P11D"),
+            (DurationParts { minutes: 17, seconds: 2, ..Default::default() }, "PT17M2S"),
+            (DurationParts { hours: 21, ..Default::default() }, "PT21H"),
+        ];
+        for (parts, expected) in cases {
+            assert_eq!(format_duration(parts, DurationStyle::Iso8601, Locale::De), expected);
+        }
+    }
+
+    #[test]
+    fn accept_language() {
+        assert_eq!(Locale::from_accept_language(""), Locale::En);
+        assert_eq!(Locale::from_accept_language("de"), Locale::De);
+        assert_eq!(Locale::from_accept_language("pt-BR,pt;q=0.9"), Locale::Pt);
+        assert_eq!(Locale::from_accept_language("ja, fr-CH;q=0.9, en;q=0.8"), Locale::Fr);
+        assert_eq!(Locale::from_accept_language("en;q=0.5, es;q=0.7"), Locale::Es);
+        assert_eq!(Locale::from_accept_language("de;q=0, *"), Locale::En);
+    }
+
+    #[test]
+    fn calendar_locales() {
+        let cases = [
+            (Locale::En, "18Y 9M 1w 3d 0h 0m 0s", "18 years, 9 months, 1 week, 3 days"),
+            (
+                Locale::De,
+                "18 J. 9 Mon. 1 Wo. 3 T 0 Std. 0 Min. 0 Sek.",
+                "18 Jahre, 9 Monate, 1 Woche, 3 Tage",
+            ),
+            (
+                Locale::Es,
+                "18 a 9 mes 1 sem 3 d 0 h 0 min 0 s",
+                "18 años, 9 meses, 1 semana, 3 días",
+            ),
+            (
+                Locale::Fr,
+                "18 a 9 mois 1 sem 3 j 0 h 0 min 0 s",
+                "18 ans, 9 mois, 1 semaine, 3 jours",
+            ),
+            (Locale::Pt, "18 a 9 m 1 sem 3 d 0 h 0 min 0 s", "18 anos, 9 meses, 1 semana, 3 dias"),
+        ];
+        for (locale, compact, long) in cases {
+            assert_eq!(format_duration(CALENDAR_PARTS, DurationStyle::Compact, locale), compact);
+            assert_eq!(format_duration(CALENDAR_PARTS, DurationStyle::Long, locale), long);
+        }
+        assert_eq!(
+            format_duration(CALENDAR_PARTS, DurationStyle::Iso8601, Locale::En),
+            "P18Y9M10D"
+        );
+    }
+
+    #[test]
+    fn calendar_decades() {
+        let a = Utc.with_ymd_and_hms(1930, 4, 3, 1, 1, 1).unwrap().naive_utc();
+        let b = Utc.with_ymd_and_hms(1964, 1, 20, 2, 2, 2).unwrap().naive_utc();
+        let parts = calendar_duration_parts(a, b);
+        assert_eq!(
+            format_duration(parts, DurationStyle::Compact, Locale::En),
+            "33Y 9M 2w 3d 1h 1m 1s"
+        );
+    }
+
+    #[test]
+    fn calendar_end_of_month() {
+        let jan_31 = Utc.with_ymd_and_hms(2025, 1, 31, 0, 0, 0).unwrap().naive_utc();
+        let feb_28 = Utc.with_ymd_and_hms(2025, 2, 28, 0, 0, 0).unwrap().naive_utc();
+        let mar_1 = Utc.with_ymd_and_hms(2025, 3, 1, 0, 0, 0).unwrap().naive_utc();
+        let mar_31 = Utc.with_ymd_and_hms(2025, 3, 31, 0, 0, 0).unwrap().naive_utc();
+
+        let one_month = DurationParts { months: 1, ..Default::default() };
+        assert_eq!(calendar_duration_parts(jan_31, feb_28), one_month);
+        assert_eq!(calendar_duration_parts(jan_31, mar_1), DurationParts { days: 1, ..one_month });
+        assert_eq!(
+            calendar_duration_parts(jan_31, mar_31),
+            DurationParts { months: 2, ..Default::default() }
+        );
+    }
+
+    #[test]
+    fn calendar_leap_year() {
+        let feb_29 = Utc.with_ymd_and_hms(2028, 2, 29, 12, 0, 0).unwrap().naive_utc();
+        let feb_28 = Utc.with_ymd_and_hms(2029, 2, 28, 12, 0, 0).unwrap().naive_utc();
+        let mar_1 = Utc.with_ymd_and_hms(2029, 3, 1, 11, 0, 0).unwrap().naive_utc();
+
+        assert_eq!(
+            calendar_duration_parts(feb_29, feb_28),
+            DurationParts { years: 1, ..Default::default() }
+        );
+        assert_eq!(
+            calendar_duration_parts(feb_29, mar_1),
+            DurationParts { years: 1, hours: 23, ..Default::default() }
+        );
+    }
+
+    #[test]
+    fn calendar_borrowing() {
+        let a = Utc.with_ymd_and_hms(2006, 11, 6, 20, 33, 15).unwrap().naive_utc();
+        let b = Utc.with_ymd_and_hms(2025, 8, 17, 7, 27, 49).unwrap().naive_utc();
+        assert_eq!(
+            calendar_duration_parts(a, b),
+            DurationParts {
+                years: 18,
+                months: 9,
+                weeks: 1,
+                days: 3,
+                hours: 10,
+                minutes: 54,
+                seconds: 34,
+                milliseconds: 0,
+            }
+        );
+        assert_eq!(calendar_duration_parts(b, a), calendar_duration_parts(a, b));
+    }
+
+    fn arb_datetime() -> impl Strategy<Value = chrono::NaiveDateTime> {
+        // Between years 1900 and ~2200, with millisecond precision.
+        (0i64..(300 * 365 * 24 * 60 * 60 * 1000)).prop_map(|millis| {
+            NaiveDate::from_ymd_opt(1900, 1, 1)
+                .unwrap()
+                .and_hms_opt(0, 0, 0)
+                .unwrap()
+                + TimeDelta::milliseconds(millis)
+        })
+    }
+
+    proptest! {
+        /// Adding the breakdown back onto `from` with chrono's arithmetic gives `to`.
+        #[test]
+        fn calendar_roundtrip(a in arb_datetime(), b in arb_datetime()) {
+            let (from, to) = if a <= b { (a, b) } else { (b, a) };
+            let parts = calendar_duration_parts(from, to);
+
+            let months = (parts.years * 12 + parts.months) as u32;
+            let rest = TimeDelta::weeks(parts.weeks as i64)
+                + TimeDelta::days(parts.days as i64)
+                + TimeDelta::hours(parts.hours as i64)
+                + TimeDelta::minutes(parts.minutes as i64)
+                + TimeDelta::seconds(parts.seconds as i64)
+                + TimeDelta::milliseconds(parts.milliseconds as i64);
+            let sum = from.checked_add_months(Months::new(months)).unwrap() + rest;
+
+            prop_assert_eq!(sum, to);
+        }
+
+        /// Every unit is within its range, and the months are as many as possible.
+        #[test]
+        fn calendar_normalized(a in arb_datetime(), b in arb_datetime()) {
+            let (from, to) = if a <= b { (a, b) } else { (b, a) };
+            let parts = calendar_duration_parts(from, to);
+
+            prop_assert!(parts.years >= 0);
+            prop_assert!((0..12).contains(&parts.months));
+            prop_assert!((0..5).contains(&parts.weeks));
+            prop_assert!((0..7).contains(&parts.days));
+            prop_assert!((0..24).contains(&parts.hours));
+            prop_assert!((0..60).contains(&parts.minutes));
+            prop_assert!((0..60).contains(&parts.seconds));
+            prop_assert!((0..1000).contains(&parts.milliseconds));
+
+            let one_more_month = (parts.years * 12 + parts.months + 1) as u32;
+            prop_assert!(from.checked_add_months(Months::new(one_more_month)).unwrap() > to);
+        }
+    }
+
+    #[test]
+    fn signed_future() {
+        let now = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap().naive_utc();
+        let target = Utc.with_ymd_and_hms(2025, 1, 4, 2, 0, 0).unwrap().naive_utc();
+        let duration = SignedDuration::until(now, target);
+
+        assert!(!duration.overdue);
+        assert_eq!(
+            format_signed_duration(duration, DurationStyle::Compact, Locale::En),
+            "3d 2h 0m 0s"
+        );
+    }
+
+    #[test]
+    fn signed_overdue() {
+        let now = Utc.with_ymd_and_hms(2025, 1, 4, 2, 0, 0).unwrap().naive_utc();
+        let target = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap().naive_utc();
+        let duration = SignedDuration::until(now, target);
+
+        assert!(duration.overdue);
+        let cases = [
+            (DurationStyle::Compact, Locale::En, "overdue by 3d 2h 0m 0s"),
+            (DurationStyle::Long, Locale::En, "overdue by 3 days, 2 hours"),
+            (DurationStyle::Long, Locale::De, "3 Tage, 2 Stunden überfällig"),
+            (DurationStyle::Long, Locale::Es, "con 3 días, 2 horas de retraso"),
+            (DurationStyle::Long, Locale::Fr, "en retard de 3 jours, 2 heures"),
+            (DurationStyle::Long, Locale::Pt, "atrasado há 3 dias, 2 horas"),
+            (DurationStyle::Iso8601, Locale::En, "-P3DT2H"),
+        ];
+        for (style, locale, expected) in cases {
+            assert_eq!(format_signed_duration(duration, style, locale), expected);
+        }
+    }
+
+    #[test]
+    fn milliseconds() {
+        let a = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap().naive_utc()
+            + TimeDelta::milliseconds(750);
+        let b = Utc.with_ymd_and_hms(2025, 1, 1, 0, 1, 2).unwrap().naive_utc()
+            + TimeDelta::milliseconds(5);
+        let parts = duration_parts(a, b);
+        assert_eq!(
+            parts,
+            DurationParts { minutes: 1, seconds: 1, milliseconds: 255, ..Default::default() }
+        );
+
+        let cases = [
+            (DurationStyle::Compact, Locale::En, "1m 1.255s"),
+            (DurationStyle::Compact, Locale::De, "1 Min. 1,255 Sek."),
+            (DurationStyle::Long, Locale::En, "1 minute, 1.255 seconds"),
+            (DurationStyle::Long, Locale::Fr, "1 minute, 1,255 seconde"),
+            (DurationStyle::Iso8601, Locale::Fr, "PT1M1.255S"),
+        ];
+        for (style, locale, expected) in cases {
+            assert_eq!(format_duration(parts, style, locale), expected);
+        }
+
+        let only_millis = DurationParts { milliseconds: 5, ..Default::default() };
+        assert_eq!(format_duration(only_millis, DurationStyle::Iso8601, Locale::En), "PT0.005S");
+
+        let duration = SignedDuration::until(a, b).with_precision(Precision::Seconds);
+        assert_eq!(format_signed_duration(duration, DurationStyle::Compact, Locale::En), "1m 1s");
+    }
 }
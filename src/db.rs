@@ -1,43 +1,57 @@
-use sqlx::{PgPool, postgres::PgPoolOptions, query};
+use chrono::{DateTime, NaiveDate, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::migrate::{Migrate, MigrateError, Migrator};
+use sqlx::{FromRow, PgPool, postgres::PgPoolOptions, query, query_as};
 
 use crate::TimeSeriesDataEntry;
+use crate::config::{AggregateConfig, AggregatesConfig, TimeSeriesConfig};
+use crate::milestones::{Milestone, MilestoneEntry};
+use crate::webhooks::DeadLetter;
 
-pub async fn init_db() -> Result<PgPool, sqlx::Error> {
+/// Maximum number of buckets returned by a single stats query.
+const MAX_STATS_BUCKETS: i64 = 10_000;
+
+/// Migrations in `migrations/`, embedded into the binary at compile time. Applied versions are
+/// tracked by sqlx in the `_sqlx_migrations` table.
+static MIGRATOR: Migrator = sqlx::migrate!();
+
+async fn connect() -> Result<PgPool, sqlx::Error> {
     // https://docs.rs/sqlx/latest/sqlx/postgres/struct.PgConnectOptions.html
     // Get params from environment variables
     let db_url = "postgres://";
-    let pool = PgPoolOptions::new().connect(db_url).await?;
+    PgPoolOptions::new().connect(db_url).await
+}
 
-    let mut tx = pool.begin().await?;
+/// Connects to the database and applies any pending migrations.
+pub async fn init_db() -> Result<PgPool, MigrateError> {
+    let pool = connect().await?;
+    MIGRATOR.run(&pool).await?;
 
-    query(
-        "
-            CREATE TABLE IF NOT EXISTS time_series_data (
-              timestamp      TIMESTAMP WITHOUT TIME ZONE    NOT NULL,
-              page_name      TEXT                           NOT NULL,
-              datetime       TIMESTAMP WITHOUT TIME ZONE    NOT NULL,
-              click_count    BIGINT                         NOT NULL,
-              user_count     INTEGER                        NOT NULL
-            )
-            WITH (
-              tsdb.hypertable,
-              tsdb.partition_column = 'timestamp',
-              tsdb.segmentby = 'page_name',
-              tsdb.chunk_interval = '1d',
-              tsdb.orderby = 'timestamp DESC'
-            );
-        ",
-    )
-    .execute(&mut *tx)
-    .await?;
+    Ok(pool)
+}
 
-    query("CALL add_columnstore_policy('time_series_data', after => INTERVAL '1d', if_not_exists => TRUE);")
-        .execute(&mut *tx)
-        .await?;
+/// Applies any pending migrations, returning the versions and descriptions of the ones that were
+/// applied. Used by the `migrate` subcommand.
+pub async fn migrate() -> Result<Vec<(i64, String)>, MigrateError> {
+    let pool = connect().await?;
 
-    tx.commit().await?;
+    let mut conn = pool.acquire().await?;
+    conn.ensure_migrations_table().await?;
+    let applied_before = conn
+        .list_applied_migrations()
+        .await?
+        .into_iter()
+        .map(|m| m.version)
+        .collect::<Vec<_>>();
+    drop(conn);
 
-    Ok(pool)
+    MIGRATOR.run(&pool).await?;
+
+    Ok(MIGRATOR
+        .iter()
+        .filter(|m| m.migration_type.is_up_migration() && !applied_before.contains(&m.version))
+        .map(|m| (m.version, m.description.to_string()))
+        .collect())
 }
 
 pub async fn insert_time_series_page_data(
@@ -47,11 +61,8 @@ pub async fn insert_time_series_page_data(
     // https://github.com/launchbadge/sqlx/blob/main/FAQ.md#how-can-i-bind-an-array-to-a-values-clause-how-can-i-do-bulk-inserts
     let timestamps = data.iter().map(|e| e.timestamp).collect::<Vec<_>>();
     let datetime = data.iter().map(|e| e.datetime).collect::<Vec<_>>();
-    let click_count = data
-        .iter()
-        .map(|e| e.click_count as i64)
-        .collect::<Vec<_>>();
-    let user_count = data.iter().map(|e| e.user_count as i32).collect::<Vec<_>>();
+    let click_count = data.iter().map(|e| e.click_count).collect::<Vec<_>>();
+    let user_count = data.iter().map(|e| e.user_count).collect::<Vec<_>>();
     let page_name = data.into_iter().map(|e| e.page_name).collect::<Vec<_>>();
 
     query(
@@ -70,3 +81,473 @@ pub async fn insert_time_series_page_data(
 
     Ok(())
 }
+
+/// Bucket width of a continuous aggregate of `time_series_data`.
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Resolution {
+    Minute,
+    Hour,
+    Day,
+}
+
+impl Resolution {
+    const ALL: [Resolution; 3] = [Resolution::Minute, Resolution::Hour, Resolution::Day];
+
+    fn view_name(self) -> &'static str {
+        match self {
+            Resolution::Minute => "time_series_data_minute",
+            Resolution::Hour => "time_series_data_hour",
+            Resolution::Day => "time_series_data_day",
+        }
+    }
+
+    fn bucket_width(self) -> &'static str {
+        match self {
+            Resolution::Minute => "1 minute",
+            Resolution::Hour => "1 hour",
+            Resolution::Day => "1 day",
+        }
+    }
+
+    /// How far back the refresh policy re-materializes buckets on each run.
+    fn refresh_start_offset(self) -> &'static str {
+        match self {
+            Resolution::Minute => "1 hour",
+            Resolution::Hour => "1 day",
+            Resolution::Day => "3 days",
+        }
+    }
+
+    /// Default time range of a stats query if one isn't given.
+    pub fn default_range(self) -> TimeDelta {
+        match self {
+            Resolution::Minute => TimeDelta::hours(6),
+            Resolution::Hour => TimeDelta::days(7),
+            Resolution::Day => TimeDelta::days(365),
+        }
+    }
+
+    fn config(self, config: &AggregatesConfig) -> &AggregateConfig {
+        match self {
+            Resolution::Minute => &config.minute,
+            Resolution::Hour => &config.hour,
+            Resolution::Day => &config.day,
+        }
+    }
+}
+
+/// Creates, updates, or drops the continuous aggregates and retention policies so that they match
+/// the config. Runs on every startup, so changing the config and restarting is enough to apply it.
+pub async fn reconcile_time_series_policies(
+    pool: &PgPool,
+    config: &TimeSeriesConfig,
+) -> Result<(), sqlx::Error> {
+    // Continuous aggregates can't be created inside a transaction, so each statement is executed
+    // on its own. All of them are idempotent.
+    for resolution in Resolution::ALL {
+        let view_name = resolution.view_name();
+        let aggregate_config = resolution.config(&config.aggregates);
+
+        if !aggregate_config.enabled {
+            query(&format!("DROP MATERIALIZED VIEW IF EXISTS {};", view_name))
+                .execute(pool)
+                .await?;
+            continue;
+        }
+
+        // `last()` picks the datetime at the end of the bucket.
+        query(&format!(
+            "
+                CREATE MATERIALIZED VIEW IF NOT EXISTS {view_name}
+                WITH (timescaledb.continuous, timescaledb.materialized_only = false) AS
+                SELECT
+                  time_bucket(INTERVAL '{bucket_width}', timestamp)    AS bucket,
+                  page_name,
+                  min(click_count)                                     AS click_count_min,
+                  max(click_count)                                     AS click_count_max,
+                  avg(user_count)::DOUBLE PRECISION                    AS user_count_avg,
+                  max(user_count)                                      AS user_count_max,
+                  last(datetime, timestamp)                            AS datetime
+                FROM time_series_data
+                GROUP BY bucket, page_name
+                WITH NO DATA;
+            ",
+            bucket_width = resolution.bucket_width(),
+        ))
+        .execute(pool)
+        .await?;
+
+        // Remove then re-add so that changes to the policy are picked up.
+        query("SELECT remove_continuous_aggregate_policy($1, if_exists => TRUE);")
+            .bind(view_name)
+            .execute(pool)
+            .await?;
+        query(
+            "
+                SELECT add_continuous_aggregate_policy(
+                  $1,
+                  start_offset => $2::INTERVAL,
+                  end_offset => $3::INTERVAL,
+                  schedule_interval => $3::INTERVAL
+                );
+            ",
+        )
+        .bind(view_name)
+        .bind(resolution.refresh_start_offset())
+        .bind(resolution.bucket_width())
+        .execute(pool)
+        .await?;
+
+        reconcile_retention_policy(pool, view_name, aggregate_config.retention.as_deref()).await?;
+    }
+
+    reconcile_retention_policy(pool, "time_series_data", config.raw_retention.as_deref()).await?;
+
+    Ok(())
+}
+
+async fn reconcile_retention_policy(
+    pool: &PgPool,
+    relation: &str,
+    drop_after: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    query("SELECT remove_retention_policy($1, if_exists => TRUE);")
+        .bind(relation)
+        .execute(pool)
+        .await?;
+
+    if let Some(drop_after) = drop_after {
+        query("SELECT add_retention_policy($1, drop_after => $2::INTERVAL);")
+            .bind(relation)
+            .bind(drop_after)
+            .execute(pool)
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// A page's rolled up time series data over one bucket.
+#[derive(Serialize, FromRow)]
+pub struct TimeSeriesStats {
+    pub bucket: DateTime<Utc>,
+    pub click_count_min: i64,
+    pub click_count_max: i64,
+    pub user_count_avg: f64,
+    pub user_count_max: i32,
+    pub datetime: DateTime<Utc>,
+}
+
+/// Fetches a page's stats between `from` and `to`, reading from the continuous aggregate of the
+/// given resolution. If that aggregate is disabled, the raw data is bucketed instead, which is a
+/// lot slower over long ranges.
+pub async fn query_time_series_stats(
+    pool: &PgPool,
+    config: &TimeSeriesConfig,
+    page_name: &str,
+    resolution: Resolution,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<Vec<TimeSeriesStats>, sqlx::Error> {
+    let source = if resolution.config(&config.aggregates).enabled {
+        format!(
+            "
+                SELECT bucket, page_name, click_count_min, click_count_max, user_count_avg,
+                  user_count_max, datetime
+                FROM {}
+            ",
+            resolution.view_name()
+        )
+    } else {
+        format!(
+            "
+                SELECT
+                  time_bucket(INTERVAL '{}', timestamp) AS bucket,
+                  page_name,
+                  min(click_count) AS click_count_min,
+                  max(click_count) AS click_count_max,
+                  avg(user_count)::DOUBLE PRECISION AS user_count_avg,
+                  max(user_count) AS user_count_max,
+                  last(datetime, timestamp) AS datetime
+                FROM time_series_data
+                WHERE page_name = $1 AND timestamp >= $2 AND timestamp < $3
+                GROUP BY bucket, page_name
+            ",
+            resolution.bucket_width()
+        )
+    };
+
+    // Timestamps are stored as UTC without a time zone.
+    query_as::<_, TimeSeriesStats>(&format!(
+        "
+            SELECT
+              bucket AT TIME ZONE 'UTC' AS bucket,
+              click_count_min,
+              click_count_max,
+              user_count_avg,
+              user_count_max,
+              datetime AT TIME ZONE 'UTC' AS datetime
+            FROM ({}) AS stats
+            WHERE page_name = $1 AND bucket >= $2 AND bucket < $3
+            ORDER BY bucket
+            LIMIT $4;
+        ",
+        source
+    ))
+    .bind(page_name)
+    .bind(from.naive_utc())
+    .bind(to.naive_utc())
+    .bind(MAX_STATS_BUCKETS)
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn insert_milestone(
+    pool: &PgPool,
+    page_name: &str,
+    milestone: Milestone,
+    datetime: DateTime<Utc>,
+    timestamp: DateTime<Utc>,
+) -> Result<(), sqlx::Error> {
+    query(
+        "
+            INSERT INTO milestones(timestamp, page_name, kind, value, datetime)
+            VALUES ($1, $2, $3, $4, $5);
+        ",
+    )
+    .bind(timestamp.naive_utc())
+    .bind(page_name)
+    .bind(milestone.kind())
+    .bind(milestone.value())
+    .bind(datetime.naive_utc())
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Fetches a page's latest milestones, newest first.
+pub async fn query_milestones(
+    pool: &PgPool,
+    page_name: &str,
+    limit: i64,
+) -> Result<Vec<MilestoneEntry>, sqlx::Error> {
+    let rows = query_as::<_, (i64, DateTime<Utc>, String, Option<i64>, DateTime<Utc>)>(
+        "
+            SELECT
+              id,
+              timestamp AT TIME ZONE 'UTC',
+              kind,
+              value,
+              datetime AT TIME ZONE 'UTC'
+            FROM milestones
+            WHERE page_name = $1
+            ORDER BY timestamp DESC, id DESC
+            LIMIT $2;
+        ",
+    )
+    .bind(page_name)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    // Rows of unknown kinds (e.g. added by a newer version) are skipped.
+    Ok(rows
+        .into_iter()
+        .filter_map(|(id, timestamp, kind, value, datetime)| {
+            Some(MilestoneEntry {
+                id,
+                timestamp,
+                milestone: Milestone::from_kind(&kind, value)?,
+                datetime,
+            })
+        })
+        .collect())
+}
+
+/// A page's stats over a day.
+#[derive(Serialize, FromRow, Debug, Clone)]
+pub struct DailySummary {
+    #[serde(skip)]
+    pub page_name: String,
+    pub date: NaiveDate,
+    pub clicks_added: i64,
+    pub seconds_added: f64,
+    pub peak_user_count: i32,
+}
+
+/// Computes and stores the summaries of every page over the day, from the time series data.
+/// Returns the summaries that were stored, which are none if the day was already summarized.
+///
+/// Clicks are counted from the last snapshot before the day (if any), so that clicks made between
+/// the previous day's last snapshot and this day's first one aren't left out of both days.
+pub async fn insert_daily_summaries(
+    pool: &PgPool,
+    date: NaiveDate,
+) -> Result<Vec<DailySummary>, sqlx::Error> {
+    let start = date.and_hms_opt(0, 0, 0).unwrap();
+    let end = start + TimeDelta::days(1);
+    query_as::<_, DailySummary>(
+        "
+            WITH day AS (
+              SELECT
+                page_name,
+                first(click_count, timestamp) AS first_click_count,
+                last(click_count, timestamp) AS last_click_count,
+                first(datetime, timestamp) AS first_datetime,
+                last(datetime, timestamp) AS last_datetime,
+                MAX(user_count) AS peak_user_count
+              FROM time_series_data
+              WHERE timestamp >= $2 AND timestamp < $3
+              GROUP BY page_name
+            ),
+            summaries AS (
+              SELECT
+                day.page_name,
+                day.last_click_count - COALESCE(previous.click_count, day.first_click_count)
+                  AS clicks_added,
+                EXTRACT(EPOCH FROM
+                  day.last_datetime - COALESCE(previous.datetime, day.first_datetime)
+                )::DOUBLE PRECISION AS seconds_added,
+                day.peak_user_count
+              FROM day
+              LEFT JOIN LATERAL (
+                SELECT click_count, datetime
+                FROM time_series_data
+                WHERE page_name = day.page_name AND timestamp < $2
+                ORDER BY timestamp DESC
+                LIMIT 1
+              ) previous ON TRUE
+            )
+            INSERT INTO daily_summaries(
+              page_name, date, clicks_added, seconds_added, peak_user_count
+            )
+            SELECT page_name, $1, clicks_added, seconds_added, peak_user_count
+            FROM summaries
+            ON CONFLICT (page_name, date) DO NOTHING
+            RETURNING page_name, date, clicks_added, seconds_added, peak_user_count;
+        ",
+    )
+    .bind(date)
+    .bind(start)
+    .bind(end)
+    .fetch_all(pool)
+    .await
+}
+
+/// Fetches a page's latest daily summaries, newest first.
+pub async fn query_daily_summaries(
+    pool: &PgPool,
+    page_name: &str,
+    limit: i64,
+) -> Result<Vec<DailySummary>, sqlx::Error> {
+    query_as::<_, DailySummary>(
+        "
+            SELECT page_name, date, clicks_added, seconds_added, peak_user_count
+            FROM daily_summaries
+            WHERE page_name = $1
+            ORDER BY date DESC
+            LIMIT $2;
+        ",
+    )
+    .bind(page_name)
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn insert_webhook_dead_letter(
+    pool: &PgPool,
+    dead_letter: &DeadLetter,
+) -> Result<(), sqlx::Error> {
+    query(
+        "
+            INSERT INTO webhook_dead_letters(
+              timestamp, url, event, delivery_id, payload, attempts, last_error
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7);
+        ",
+    )
+    .bind(dead_letter.timestamp.naive_utc())
+    .bind(&dead_letter.url)
+    .bind(&dead_letter.event)
+    .bind(&dead_letter.delivery_id)
+    .bind(&dead_letter.payload)
+    .bind(dead_letter.attempts)
+    .bind(&dead_letter.last_error)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Fetches every dead letter along with its ID, oldest first.
+pub async fn query_webhook_dead_letters(
+    pool: &PgPool,
+) -> Result<Vec<(i64, DeadLetter)>, sqlx::Error> {
+    let rows = query_as::<
+        _,
+        (
+            i64,
+            DateTime<Utc>,
+            String,
+            String,
+            String,
+            String,
+            i32,
+            String,
+        ),
+    >(
+        "
+            SELECT
+              id,
+              timestamp AT TIME ZONE 'UTC',
+              url,
+              event,
+              delivery_id,
+              payload,
+              attempts,
+              last_error
+            FROM webhook_dead_letters
+            ORDER BY id;
+        ",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(
+            |(id, timestamp, url, event, delivery_id, payload, attempts, last_error)| {
+                (
+                    id,
+                    DeadLetter {
+                        timestamp,
+                        url,
+                        event,
+                        delivery_id,
+                        payload,
+                        attempts,
+                        last_error,
+                    },
+                )
+            },
+        )
+        .collect())
+}
+
+pub async fn delete_webhook_dead_letter(pool: &PgPool, id: i64) -> Result<(), sqlx::Error> {
+    query("DELETE FROM webhook_dead_letters WHERE id = $1;")
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+/// Checks that a connection can be made and used.
+pub async fn ping(pool: &PgPool) -> Result<(), sqlx::Error> {
+    query("SELECT 1;").execute(pool).await?;
+    Ok(())
+}
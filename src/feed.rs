@@ -0,0 +1,116 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+
+/// An entry of an Atom feed.
+pub struct FeedEntry {
+    /// Unique and permanent, so that feed readers don't show the same entry twice.
+    pub id: String,
+    pub title: String,
+    pub updated: DateTime<Utc>,
+    pub summary: String,
+}
+
+/// Generates an Atom (RFC 4287) feed containing the entries, which should be sorted from newest to
+/// oldest. `link` is the page the feed is about, and may be relative to the feed's URL.
+pub fn atom_feed(id: &str, title: &str, link: &str, entries: &[FeedEntry]) -> String {
+    // The feed changes whenever a new entry is added, so it was last updated when the newest entry
+    // was.
+    let updated = entries
+        .iter()
+        .map(|entry| entry.updated)
+        .max()
+        .unwrap_or(DateTime::UNIX_EPOCH);
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+    xml.push_str(&format!("  <id>{}</id>\n", escape_xml(id)));
+    xml.push_str(&format!("  <title>{}</title>\n", escape_xml(title)));
+    xml.push_str(&format!(
+        "  <updated>{}</updated>\n",
+        format_datetime(updated)
+    ));
+    xml.push_str(&format!(
+        "  <link rel=\"alternate\" href=\"{}\"/>\n",
+        escape_xml(link)
+    ));
+    xml.push_str("  <author><name>update-countdown</name></author>\n");
+
+    for entry in entries {
+        xml.push_str("  <entry>\n");
+        xml.push_str(&format!("    <id>{}</id>\n", escape_xml(&entry.id)));
+        xml.push_str(&format!(
+            "    <title>{}</title>\n",
+            escape_xml(&entry.title)
+        ));
+        xml.push_str(&format!(
+            "    <updated>{}</updated>\n",
+            format_datetime(entry.updated)
+        ));
+        xml.push_str(&format!(
+            "    <summary>{}</summary>\n",
+            escape_xml(&entry.summary)
+        ));
+        xml.push_str("  </entry>\n");
+    }
+
+    xml.push_str("</feed>\n");
+    xml
+}
+
+fn format_datetime(datetime: DateTime<Utc>) -> String {
+    datetime.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// Escapes text for use in XML element content and attribute values.
+pub fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use crate::feed::{FeedEntry, atom_feed};
+
+    #[test]
+    fn entries() {
+        let entries = [FeedEntry {
+            id: "urn:update-countdown:battlebit:milestone:1".to_string(),
+            title: "BattleBit <Remastered> & co reached 10000 clicks".to_string(),
+            updated: Utc.with_ymd_and_hms(2026, 4, 15, 12, 0, 0).unwrap(),
+            summary: "The update is now expected on 2026-05-01.".to_string(),
+        }];
+        let xml = atom_feed(
+            "urn:update-countdown:battlebit",
+            "BattleBit Remastered",
+            "/battlebit",
+            &entries,
+        );
+
+        assert!(xml.contains("<updated>2026-04-15T12:00:00Z</updated>\n  <link"));
+        assert!(
+            xml.contains(
+                "<title>BattleBit &lt;Remastered&gt; &amp; co reached 10000 clicks</title>"
+            )
+        );
+        assert!(xml.contains("<id>urn:update-countdown:battlebit:milestone:1</id>"));
+        assert!(xml.ends_with("  </entry>\n</feed>\n"));
+    }
+
+    #[test]
+    fn empty() {
+        let xml = atom_feed("urn:update-countdown:battlebit", "", "/battlebit", &[]);
+        assert!(xml.contains("<updated>1970-01-01T00:00:00Z</updated>"));
+        assert!(!xml.contains("<entry>"));
+    }
+}
@@ -0,0 +1,215 @@
+use std::fs::OpenOptions;
+use std::path::Path;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::PgPool;
+use tokio::time::timeout;
+
+use crate::db::ping;
+
+/// A task is considered stuck if it hasn't succeeded for this many of its intervals.
+const MAX_MISSED_INTERVALS: u32 = 3;
+const DATABASE_PING_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Status of a single component checked by `/readyz`.
+#[derive(Serialize)]
+pub struct ComponentStatus {
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_success: Option<DateTime<Utc>>,
+    /// Number of times a background task has failed and been restarted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failures: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+impl ComponentStatus {
+    pub fn ok() -> Self {
+        Self {
+            ok: true,
+            error: None,
+            last_success: None,
+            failures: None,
+            last_error: None,
+        }
+    }
+
+    pub fn error(error: impl ToString) -> Self {
+        Self {
+            ok: false,
+            error: Some(error.to_string()),
+            last_success: None,
+            failures: None,
+            last_error: None,
+        }
+    }
+}
+
+/// Checks that the file can be written to, without modifying it.
+pub fn check_writable(path: impl AsRef<Path>) -> ComponentStatus {
+    match OpenOptions::new().append(true).open(path) {
+        Ok(_) => ComponentStatus::ok(),
+        Err(err) => ComponentStatus::error(err),
+    }
+}
+
+pub async fn check_database(pool: &PgPool) -> ComponentStatus {
+    match timeout(DATABASE_PING_TIMEOUT, ping(pool)).await {
+        Ok(Ok(())) => ComponentStatus::ok(),
+        Ok(Err(err)) => ComponentStatus::error(err),
+        Err(_) => ComponentStatus::error("timed out"),
+    }
+}
+
+/// Health of the background tasks spawned in `main`.
+pub struct TasksHealth {
+    pub save: TaskHealth,
+    pub insert_time_series_data: TaskHealth,
+    pub daily_summary: TaskHealth,
+    pub timed_events: TaskHealth,
+}
+
+/// Health of a background task that does something every `interval`. Updated by the task and its
+/// supervisor, and read by `/readyz`.
+pub struct TaskHealth {
+    interval: Duration,
+    running: AtomicBool,
+    /// Unix timestamp in milliseconds of when the task first started or last succeeded, whichever
+    /// is later. Restarts don't count as progress, so that a task that keeps failing is reported as
+    /// stuck.
+    last_progress: AtomicI64,
+    /// Unix timestamp in milliseconds of the last success, or `i64::MIN` if it hasn't succeeded
+    /// yet.
+    last_success: AtomicI64,
+    failures: AtomicU64,
+    last_error: Mutex<Option<String>>,
+}
+
+impl TaskHealth {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            running: AtomicBool::new(false),
+            last_progress: AtomicI64::new(i64::MIN),
+            last_success: AtomicI64::new(i64::MIN),
+            failures: AtomicU64::new(0),
+            last_error: Mutex::new(None),
+        }
+    }
+
+    /// Marks the task as running until the returned guard is dropped, which happens when the task
+    /// returns, panics, or is aborted.
+    pub fn start(&self) -> RunningGuard<'_> {
+        self.running.store(true, Ordering::SeqCst);
+        let _ = self.last_progress.compare_exchange(
+            i64::MIN,
+            Utc::now().timestamp_millis(),
+            Ordering::SeqCst,
+            Ordering::SeqCst,
+        );
+        RunningGuard(self)
+    }
+
+    pub fn record_success(&self) {
+        let now = Utc::now().timestamp_millis();
+        self.last_success.store(now, Ordering::SeqCst);
+        self.last_progress.store(now, Ordering::SeqCst);
+    }
+
+    pub fn record_failure(&self, error: &str) {
+        self.failures.fetch_add(1, Ordering::SeqCst);
+        *self.last_error.lock().unwrap() = Some(error.to_string());
+    }
+
+    pub fn status(&self, now: DateTime<Utc>) -> ComponentStatus {
+        let last_success = match self.last_success.load(Ordering::SeqCst) {
+            i64::MIN => None,
+            millis => DateTime::from_timestamp_millis(millis),
+        };
+        let since_progress = now
+            .timestamp_millis()
+            .saturating_sub(self.last_progress.load(Ordering::SeqCst));
+        let max_since_progress = (self.interval * MAX_MISSED_INTERVALS).as_millis() as i64;
+
+        let mut status = if !self.running.load(Ordering::SeqCst) {
+            ComponentStatus::error("not running")
+        } else if since_progress > max_since_progress {
+            ComponentStatus::error(format!(
+                "no success in the last {} intervals",
+                MAX_MISSED_INTERVALS
+            ))
+        } else {
+            ComponentStatus::ok()
+        };
+        status.last_success = last_success;
+        status.failures = Some(self.failures.load(Ordering::SeqCst));
+        status.last_error = self.last_error.lock().unwrap().clone();
+        status
+    }
+}
+
+pub struct RunningGuard<'a>(&'a TaskHealth);
+
+impl Drop for RunningGuard<'_> {
+    fn drop(&mut self) {
+        self.0.running.store(false, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::{TimeDelta, Utc};
+
+    use crate::health::TaskHealth;
+
+    #[test]
+    fn not_started() {
+        let health = TaskHealth::new(Duration::from_secs(3));
+        assert!(!health.status(Utc::now()).ok);
+    }
+
+    #[test]
+    fn running() {
+        let health = TaskHealth::new(Duration::from_secs(3));
+        let _guard = health.start();
+        assert!(health.status(Utc::now()).ok);
+    }
+
+    #[test]
+    fn stopped() {
+        let health = TaskHealth::new(Duration::from_secs(3));
+        drop(health.start());
+        assert!(!health.status(Utc::now()).ok);
+    }
+
+    #[test]
+    fn stuck() {
+        let health = TaskHealth::new(Duration::from_secs(3));
+        let _guard = health.start();
+        health.record_success();
+        let status = health.status(Utc::now() + TimeDelta::seconds(10));
+        assert!(!status.ok);
+        assert!(status.last_success.is_some());
+    }
+
+    #[test]
+    fn restarted() {
+        let health = TaskHealth::new(Duration::from_secs(3));
+        drop(health.start());
+        health.record_failure("failed");
+        let _guard = health.start();
+        // Restarting doesn't count as progress.
+        let status = health.status(Utc::now() + TimeDelta::seconds(10));
+        assert!(!status.ok);
+        assert_eq!(status.failures, Some(1));
+    }
+}
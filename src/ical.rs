@@ -0,0 +1,133 @@
+use chrono::{DateTime, Utc};
+
+/// Product identifier of the generated calendars.
+const PRODID: &str = "-//update-countdown//EN";
+/// How often calendar apps should refetch the feed, since the datetime moves with every click.
+const REFRESH_INTERVAL: &str = "PT1H";
+/// Lines longer than this many bytes have to be folded.
+const MAX_LINE_LEN: usize = 75;
+
+/// A single point in time in an iCalendar feed.
+pub struct CalendarEvent<'a> {
+    /// Stays the same when `start` changes, so that calendar apps move the existing event instead
+    /// of adding a new one.
+    pub uid: String,
+    pub summary: &'a str,
+    pub start: DateTime<Utc>,
+    /// Increases every time the event changes.
+    pub sequence: i64,
+}
+
+/// Generates an iCalendar (RFC 5545) feed containing the events.
+pub fn calendar(name: &str, events: &[CalendarEvent], now: DateTime<Utc>) -> String {
+    let mut ics = String::new();
+    push_line(&mut ics, "BEGIN:VCALENDAR");
+    push_line(&mut ics, "VERSION:2.0");
+    push_line(&mut ics, &format!("PRODID:{}", PRODID));
+    push_line(&mut ics, "CALSCALE:GREGORIAN");
+    push_line(&mut ics, "METHOD:PUBLISH");
+    push_line(&mut ics, &format!("X-WR-CALNAME:{}", escape_text(name)));
+    push_line(
+        &mut ics,
+        &format!("REFRESH-INTERVAL;VALUE=DURATION:{}", REFRESH_INTERVAL),
+    );
+    push_line(&mut ics, &format!("X-PUBLISHED-TTL:{}", REFRESH_INTERVAL));
+
+    for event in events {
+        push_line(&mut ics, "BEGIN:VEVENT");
+        push_line(&mut ics, &format!("UID:{}", escape_text(&event.uid)));
+        push_line(&mut ics, &format!("DTSTAMP:{}", format_datetime(now)));
+        push_line(
+            &mut ics,
+            &format!("DTSTART:{}", format_datetime(event.start)),
+        );
+        push_line(&mut ics, &format!("DTEND:{}", format_datetime(event.start)));
+        push_line(&mut ics, &format!("SEQUENCE:{}", event.sequence));
+        push_line(&mut ics, &format!("SUMMARY:{}", escape_text(event.summary)));
+        push_line(&mut ics, "TRANSP:TRANSPARENT");
+        push_line(&mut ics, "END:VEVENT");
+    }
+
+    push_line(&mut ics, "END:VCALENDAR");
+    ics
+}
+
+/// UTC datetime in the iCalendar format, e.g. `20260415T120000Z`.
+fn format_datetime(datetime: DateTime<Utc>) -> String {
+    datetime.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Escapes characters that have a special meaning in text values.
+fn escape_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | ';' | ',' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Appends a content line, folding it into multiple lines if it's too long. Continuation lines
+/// start with a space, which isn't counted towards their length.
+fn push_line(ics: &mut String, line: &str) {
+    let mut line_len = 0;
+    for c in line.chars() {
+        // Only fold between characters, so that multi-byte characters aren't split.
+        if line_len + c.len_utf8() > MAX_LINE_LEN {
+            ics.push_str("\r\n ");
+            line_len = 1;
+        }
+        ics.push(c);
+        line_len += c.len_utf8();
+    }
+    ics.push_str("\r\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use crate::ical::{CalendarEvent, calendar, escape_text};
+
+    #[test]
+    fn event() {
+        let now = Utc.with_ymd_and_hms(2025, 8, 17, 7, 27, 49).unwrap();
+        let events = [CalendarEvent {
+            uid: "battlebit@update-countdown".to_string(),
+            summary: "BattleBit Remastered update",
+            start: Utc.with_ymd_and_hms(2026, 4, 15, 12, 0, 0).unwrap(),
+            sequence: 42,
+        }];
+        let ics = calendar("Update countdown", &events, now);
+
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+        assert!(ics.ends_with("END:VEVENT\r\nEND:VCALENDAR\r\n"));
+        assert!(ics.contains("\r\nUID:battlebit@update-countdown\r\n"));
+        assert!(ics.contains("\r\nDTSTAMP:20250817T072749Z\r\n"));
+        assert!(ics.contains("\r\nDTSTART:20260415T120000Z\r\n"));
+        assert!(ics.contains("\r\nSEQUENCE:42\r\n"));
+        assert!(ics.contains("\r\nSUMMARY:BattleBit Remastered update\r\n"));
+    }
+
+    #[test]
+    fn escaping() {
+        assert_eq!(escape_text("a, b; c\\d\r\ne"), r"a\, b\; c\\d\ne");
+    }
+
+    #[test]
+    fn folding() {
+        let now = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+        let ics = calendar(&"é".repeat(100), &[], now);
+        for line in ics.split("\r\n") {
+            assert!(line.len() <= 75, "{:?} is too long", line);
+        }
+        assert!(ics.contains("\r\n é"));
+    }
+}
@@ -0,0 +1,78 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use hashbrown::HashMap;
+
+/// How long the result of a request is remembered for its idempotency key. Retries are expected
+/// to happen well within this.
+pub const IDEMPOTENCY_KEY_TTL: Duration = Duration::from_secs(60 * 10);
+/// Longest idempotency key accepted, to bound the memory used by each one.
+pub const MAX_IDEMPOTENCY_KEY_LEN: usize = 255;
+
+/// Results of requests by page and idempotency key, so that retried requests get the original
+/// result instead of being applied twice.
+pub struct IdempotencyCache<T> {
+    inner: Mutex<Inner<T>>,
+}
+
+struct Inner<T> {
+    entries: HashMap<(String, String), (Instant, T)>,
+    last_pruned: Instant,
+}
+
+impl<T: Clone> IdempotencyCache<T> {
+    pub fn new(now: Instant) -> Self {
+        Self {
+            inner: Mutex::new(Inner {
+                entries: HashMap::new(),
+                last_pruned: now,
+            }),
+        }
+    }
+
+    /// The result stored for the key, unless it has expired.
+    pub fn get(&self, page_name: &str, key: &str, now: Instant) -> Option<T> {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .entries
+            .get(&(page_name.to_string(), key.to_string()))
+            .filter(|(stored_at, _)| now.duration_since(*stored_at) < IDEMPOTENCY_KEY_TTL)
+            .map(|(_, value)| value.clone())
+    }
+
+    /// Stores the result for the key, dropping expired ones every `IDEMPOTENCY_KEY_TTL`.
+    pub fn insert(&self, page_name: &str, key: &str, value: T, now: Instant) {
+        let mut inner = self.inner.lock().unwrap();
+        if now.duration_since(inner.last_pruned) >= IDEMPOTENCY_KEY_TTL {
+            inner
+                .entries
+                .retain(|_, (stored_at, _)| now.duration_since(*stored_at) < IDEMPOTENCY_KEY_TTL);
+            inner.last_pruned = now;
+        }
+        inner
+            .entries
+            .insert((page_name.to_string(), key.to_string()), (now, value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use crate::idempotency::{IDEMPOTENCY_KEY_TTL, IdempotencyCache};
+
+    #[test]
+    fn expires() {
+        let now = Instant::now();
+        let cache = IdempotencyCache::new(now);
+        cache.insert("battlebit", "abc", 1, now);
+
+        assert_eq!(cache.get("battlebit", "abc", now), Some(1));
+        assert_eq!(cache.get("battlebit", "def", now), None);
+        assert_eq!(cache.get("other", "abc", now), None);
+        assert_eq!(
+            cache.get("battlebit", "abc", now + IDEMPOTENCY_KEY_TTL),
+            None
+        );
+    }
+}
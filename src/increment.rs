@@ -0,0 +1,350 @@
+use chrono::{DateTime, TimeDelta, Utc};
+use rand::Rng;
+use rand_distr::{Distribution, Exp, Normal};
+use serde::{Deserialize, Serialize};
+
+/// Longest a single click can push a page's datetime, so that a badly configured distribution
+/// can't push it out of range.
+pub const MAX_INCREMENT_SECONDS: f64 = 60.0 * 60.0 * 24.0 * 365.0;
+
+/// How far a click pushes a page's datetime, which gives each page its own "personality". Set
+/// per page under `increment` in the save file, e.g.
+/// `{"type": "normal", "mean_seconds": 1800, "std_dev_seconds": 300}`.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum IncrementDistribution {
+    /// Anywhere between the two, equally likely.
+    Uniform { min_seconds: f64, max_seconds: f64 },
+    /// Usually close to the mean: within one standard deviation about two thirds of the time.
+    /// Negative samples are rounded up to zero.
+    Normal {
+        mean_seconds: f64,
+        std_dev_seconds: f64,
+    },
+    /// Usually short, with the odd long one.
+    Exponential { mean_seconds: f64 },
+    /// One of the choices, picked with a probability proportional to its weight.
+    Weighted { choices: Vec<WeightedChoice> },
+    /// Always the same.
+    Fixed { seconds: f64 },
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct WeightedChoice {
+    pub seconds: f64,
+    pub weight: f64,
+}
+
+impl Default for IncrementDistribution {
+    /// Between 25 and 35 minutes.
+    fn default() -> Self {
+        IncrementDistribution::Uniform {
+            min_seconds: 25.0 * 60.0,
+            max_seconds: 35.0 * 60.0,
+        }
+    }
+}
+
+/// Checks that an amount of seconds is within what a click can add.
+fn validate_seconds(name: &str, seconds: f64) -> Result<(), String> {
+    if !(0.0..=MAX_INCREMENT_SECONDS).contains(&seconds) {
+        return Err(format!(
+            "`{}` must be between 0 and {}, got {}",
+            name, MAX_INCREMENT_SECONDS, seconds
+        ));
+    }
+    Ok(())
+}
+
+impl IncrementDistribution {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Checks that the parameters make sense, so that sampling can't fail.
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            IncrementDistribution::Uniform {
+                min_seconds,
+                max_seconds,
+            } => {
+                validate_seconds("min_seconds", *min_seconds)?;
+                validate_seconds("max_seconds", *max_seconds)?;
+                if min_seconds > max_seconds {
+                    return Err("`min_seconds` must not be more than `max_seconds`".to_string());
+                }
+            }
+            IncrementDistribution::Normal {
+                mean_seconds,
+                std_dev_seconds,
+            } => {
+                validate_seconds("mean_seconds", *mean_seconds)?;
+                validate_seconds("std_dev_seconds", *std_dev_seconds)?;
+            }
+            IncrementDistribution::Exponential { mean_seconds } => {
+                validate_seconds("mean_seconds", *mean_seconds)?;
+                if *mean_seconds == 0.0 {
+                    return Err("`mean_seconds` must be more than 0".to_string());
+                }
+            }
+            IncrementDistribution::Weighted { choices } => {
+                for choice in choices {
+                    validate_seconds("seconds", choice.seconds)?;
+                    if !(choice.weight.is_finite() && choice.weight >= 0.0) {
+                        return Err(format!("invalid `weight` {}", choice.weight));
+                    }
+                }
+                let total = choices.iter().map(|choice| choice.weight).sum::<f64>();
+                if total <= 0.0 {
+                    return Err("at least one choice must have a positive `weight`".to_string());
+                }
+                // Large weights can add up to infinity, which can't be sampled from.
+                if !total.is_finite() {
+                    return Err(format!("the weights add up to {}", total));
+                }
+            }
+            IncrementDistribution::Fixed { seconds } => validate_seconds("seconds", *seconds)?,
+        }
+        Ok(())
+    }
+
+    /// Samples how far a click pushes the datetime. The distribution must be valid.
+    pub fn sample(&self, rng: &mut impl Rng) -> TimeDelta {
+        let seconds = match self {
+            IncrementDistribution::Uniform {
+                min_seconds,
+                max_seconds,
+            } => rng.random_range(*min_seconds..=*max_seconds),
+            IncrementDistribution::Normal {
+                mean_seconds,
+                std_dev_seconds,
+            } => Normal::new(*mean_seconds, *std_dev_seconds)
+                .unwrap()
+                .sample(rng),
+            IncrementDistribution::Exponential { mean_seconds } => {
+                Exp::new(1.0 / mean_seconds).unwrap().sample(rng)
+            }
+            IncrementDistribution::Weighted { choices } => {
+                let total = choices.iter().map(|choice| choice.weight).sum::<f64>();
+                let mut remaining = rng.random_range(0.0..total);
+                choices
+                    .iter()
+                    .find(|choice| {
+                        remaining -= choice.weight;
+                        remaining < 0.0
+                    })
+                    // Rounding errors can leave a tiny bit remaining after the last choice.
+                    .or_else(|| choices.iter().rfind(|choice| choice.weight > 0.0))
+                    .unwrap()
+                    .seconds
+            }
+            IncrementDistribution::Fixed { seconds } => *seconds,
+        };
+        let seconds = seconds.clamp(0.0, MAX_INCREMENT_SECONDS);
+        TimeDelta::milliseconds((seconds * 1000.0).round() as i64)
+    }
+}
+
+/// Makes some clicks pull a page's datetime back instead of pushing it forward. Set per page under
+/// `hope` in the save file, e.g. `{"probability": 0.1}`.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct HopeMode {
+    /// Chance of a click subtracting time, between 0 and 1.
+    pub probability: f64,
+}
+
+impl HopeMode {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(0.0..=1.0).contains(&self.probability) {
+            return Err(format!(
+                "`probability` must be between 0 and 1, got {}",
+                self.probability
+            ));
+        }
+        Ok(())
+    }
+
+    /// Decides whether a click that would add `added` subtracts it instead, returning how far the
+    /// datetime actually moves. Subtracting never pulls the datetime before `now`, and doesn't
+    /// move it at all if it has already been reached. Must be valid.
+    pub fn apply(
+        &self,
+        added: TimeDelta,
+        datetime: DateTime<Utc>,
+        now: DateTime<Utc>,
+        rng: &mut impl Rng,
+    ) -> TimeDelta {
+        if !rng.random_bool(self.probability) {
+            return added;
+        }
+        let floor = now.min(datetime);
+        (-added).max(floor - datetime)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeDelta, TimeZone, Utc};
+    use rand::SeedableRng;
+    use rand::rngs::SmallRng;
+
+    use crate::increment::{HopeMode, IncrementDistribution, WeightedChoice};
+
+    fn samples(distribution: &IncrementDistribution, seed: u64) -> Vec<TimeDelta> {
+        let mut rng = SmallRng::seed_from_u64(seed);
+        (0..1000).map(|_| distribution.sample(&mut rng)).collect()
+    }
+
+    fn mean_seconds(samples: &[TimeDelta]) -> f64 {
+        samples
+            .iter()
+            .map(|sample| sample.num_milliseconds() as f64 / 1000.0)
+            .sum::<f64>()
+            / samples.len() as f64
+    }
+
+    #[test]
+    fn seeded() {
+        let distributions = [
+            IncrementDistribution::default(),
+            IncrementDistribution::Normal {
+                mean_seconds: 1800.0,
+                std_dev_seconds: 300.0,
+            },
+            IncrementDistribution::Exponential {
+                mean_seconds: 1800.0,
+            },
+            IncrementDistribution::Weighted {
+                choices: vec![
+                    WeightedChoice {
+                        seconds: 600.0,
+                        weight: 3.0,
+                    },
+                    WeightedChoice {
+                        seconds: 5400.0,
+                        weight: 1.0,
+                    },
+                ],
+            },
+        ];
+        for distribution in distributions {
+            assert!(distribution.validate().is_ok());
+            assert_eq!(samples(&distribution, 42), samples(&distribution, 42));
+            assert_ne!(samples(&distribution, 42), samples(&distribution, 43));
+            let mean = mean_seconds(&samples(&distribution, 42));
+            // Every distribution averages 30 minutes.
+            assert!((1800.0 * 0.9..=1800.0 * 1.1).contains(&mean), "{}", mean);
+        }
+    }
+
+    #[test]
+    fn shapes() {
+        let uniform = samples(&IncrementDistribution::default(), 1);
+        assert!(
+            uniform.iter().all(|sample| {
+                (TimeDelta::minutes(25)..=TimeDelta::minutes(35)).contains(sample)
+            })
+        );
+
+        let fixed = samples(&IncrementDistribution::Fixed { seconds: 90.5 }, 1);
+        assert!(
+            fixed
+                .iter()
+                .all(|&sample| sample == TimeDelta::milliseconds(90_500))
+        );
+
+        let weighted = IncrementDistribution::Weighted {
+            choices: vec![
+                WeightedChoice {
+                    seconds: 60.0,
+                    weight: 0.0,
+                },
+                WeightedChoice {
+                    seconds: 120.0,
+                    weight: 1.0,
+                },
+            ],
+        };
+        assert!(
+            samples(&weighted, 1)
+                .iter()
+                .all(|&sample| sample == TimeDelta::minutes(2))
+        );
+
+        // Negative samples are rounded up to zero.
+        let normal = IncrementDistribution::Normal {
+            mean_seconds: 0.0,
+            std_dev_seconds: 60.0,
+        };
+        assert!(
+            samples(&normal, 1)
+                .iter()
+                .all(|&sample| sample >= TimeDelta::zero())
+        );
+    }
+
+    #[test]
+    fn parse() {
+        let distribution: IncrementDistribution =
+            serde_json::from_str(r#"{"type": "exponential", "mean_seconds": 600}"#).unwrap();
+        assert_eq!(
+            distribution,
+            IncrementDistribution::Exponential {
+                mean_seconds: 600.0
+            }
+        );
+
+        let invalid = [
+            r#"{"type": "uniform", "min_seconds": 60, "max_seconds": 30}"#,
+            r#"{"type": "exponential", "mean_seconds": 0}"#,
+            r#"{"type": "weighted", "choices": []}"#,
+            r#"{"type": "weighted", "choices": [{"seconds": 60, "weight": 1e308}, {"seconds": 120, "weight": 1e308}]}"#,
+            r#"{"type": "fixed", "seconds": -1}"#,
+        ];
+        for json in invalid {
+            let distribution: IncrementDistribution = serde_json::from_str(json).unwrap();
+            assert!(distribution.validate().is_err(), "{}", json);
+        }
+    }
+
+    #[test]
+    fn hope() {
+        let mut rng = SmallRng::seed_from_u64(1);
+        let now = Utc.with_ymd_and_hms(2026, 10, 18, 12, 0, 0).unwrap();
+        let always = HopeMode { probability: 1.0 };
+        let never = HopeMode { probability: 0.0 };
+        let added = TimeDelta::minutes(30);
+
+        let far = now + TimeDelta::hours(1);
+        assert_eq!(never.apply(added, far, now, &mut rng), added);
+        assert_eq!(always.apply(added, far, now, &mut rng), -added);
+
+        // Can't pull the datetime before now.
+        let near = now + TimeDelta::minutes(10);
+        assert_eq!(
+            always.apply(added, near, now, &mut rng),
+            -TimeDelta::minutes(10)
+        );
+        // Nor move it once it's been reached.
+        let reached = now - TimeDelta::minutes(10);
+        assert_eq!(
+            always.apply(added, reached, now, &mut rng),
+            TimeDelta::zero()
+        );
+
+        let half = HopeMode { probability: 0.5 };
+        let subtracted = (0..1000)
+            .filter(|_| half.apply(added, far, now, &mut rng) < TimeDelta::zero())
+            .count();
+        assert!((400..=600).contains(&subtracted), "{}", subtracted);
+
+        assert!(HopeMode { probability: 1.5 }.validate().is_err());
+        assert!(
+            HopeMode {
+                probability: f64::NAN
+            }
+            .validate()
+            .is_err()
+        );
+    }
+}
@@ -1,27 +1,87 @@
+mod badge;
+mod config;
 mod datetime;
 mod db;
+mod feed;
+mod health;
+mod ical;
+mod idempotency;
+mod increment;
+mod milestones;
+mod og_image;
+mod protocol;
+mod rate_limit;
 mod routes;
+mod tasks;
+mod telemetry;
+mod timed_events;
+mod webhooks;
 
-use std::fs;
+use std::net::SocketAddr;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+use std::{fs, io};
 
-use chrono::{DateTime, Local, NaiveDateTime, Utc};
+use chrono::{DateTime, Datelike, NaiveDateTime, Utc};
 
 use axum::Router;
-use axum::routing::{get, get_service};
+use axum::http::HeaderName;
+use axum::middleware;
+use axum::routing::{delete, get, get_service, post};
 
 use hashbrown::HashMap;
+use metrics::{counter, histogram};
 use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
 use tokio::signal;
-use tokio::sync::{RwLock, broadcast};
-use tokio::time::interval;
+use tokio::sync::{Notify, RwLock, broadcast};
+use tokio::time::timeout;
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
+use tower_http::request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer};
+use tower_http::trace::TraceLayer;
 use tower_http::{compression::CompressionLayer, services::ServeDir, timeout::TimeoutLayer};
+use tracing::{error, info, warn};
 
-use crate::db::{init_db, insert_time_series_page_data};
-use crate::routes::{battlebit, root, websocket_handler};
+use crate::config::Config;
+use crate::db::{init_db, insert_time_series_page_data, migrate, reconcile_time_series_policies};
+use crate::health::{TaskHealth, TasksHealth};
+use crate::idempotency::IdempotencyCache;
+use crate::increment::{HopeMode, IncrementDistribution};
+use crate::og_image::OgImageCache;
+use crate::protocol::PageUpdate;
+use crate::rate_limit::RateLimiter;
+use crate::routes::{
+    ClickResult, badge, battlebit, calendar, calendar_all, cancel_event, click, create_event,
+    daily_summaries, embed, events, feed, healthz, list_events, og_image, page, readyz, remaining,
+    root, stats, time, websocket_handler,
+};
+use crate::tasks::{
+    DEFAULT_BACKOFF, daily_summary_task, insert_time_series_data_task, save_task, supervise,
+    timed_events_task,
+};
+use crate::telemetry::{
+    REQUEST_ID_HEADER, SAVE_DURATION_SECONDS, SAVE_FAILURES_TOTAL, init_logging, install_recorder,
+    make_request_span, track_http_requests,
+};
+use crate::timed_events::TimedEvent;
+use crate::webhooks::{WebhookQueue, retry_dead_letters, webhook_worker};
 
 const SAVE_FILE_PATH: &str = "save.json";
+const CONFIG_FILE_PATH: &str = "config.json";
+const SAVE_INTERVAL: Duration = Duration::from_secs(60 * 5);
+const TIME_SERIES_INSERT_INTERVAL: Duration = Duration::from_secs(3);
+const DAILY_SUMMARY_INTERVAL: Duration = Duration::from_secs(60 * 60 * 24);
+/// Longest the timed events task sleeps before checking again, even if no event starts or ends
+/// sooner.
+const TIMED_EVENTS_MAX_SLEEP: Duration = Duration::from_secs(60);
+/// Number of messages a page's channel holds before slow websockets start skipping them.
+const BROADCAST_CAPACITY: usize = 20000;
+/// How long to wait for websockets to close when shutting down, before saving anyway.
+const WEBSOCKET_DRAIN_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long to wait for webhook deliveries to finish their current attempt when shutting down.
+/// Deliveries waiting to be retried are dead-lettered right away.
+const WEBHOOK_DRAIN_TIMEOUT: Duration = Duration::from_secs(15);
 
 /// Snapshot of a page's data at a specific timestamp
 pub struct TimeSeriesDataEntry {
@@ -46,32 +106,147 @@ impl TimeSeriesDataEntry {
 
 #[derive(Deserialize, Serialize)]
 struct PageState {
+    /// Name of the page shown in feeds, e.g. `BattleBit Remastered`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
     datetime: DateTime<Utc>,
     #[serde(skip)]
     user_count: i32,
     click_count: i64,
+    /// Whether the datetime has been reached at some point.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    resolved: bool,
+    /// How far each click pushes the datetime.
+    #[serde(default, skip_serializing_if = "IncrementDistribution::is_default")]
+    increment: IncrementDistribution,
+    /// Whether clicks can also pull the datetime back, and how often.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    hope: Option<HopeMode>,
+    /// Latest year the datetime has been pushed into, so that a `NewYear` milestone isn't reached
+    /// again after hope mode pulls the datetime back into the previous year. Defaults to the
+    /// datetime's year.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    latest_year: Option<i32>,
+    /// Events scheduled on the page, e.g. a "double time weekend".
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    events: Vec<TimedEvent>,
+}
+
+impl PageState {
+    /// The page's title, falling back to its name if it doesn't have one.
+    fn title<'a>(&'a self, page_name: &'a str) -> &'a str {
+        self.title.as_deref().unwrap_or(page_name)
+    }
+
+    fn latest_year(&self) -> i32 {
+        self.latest_year.unwrap_or(self.datetime.year())
+    }
+
+    /// Marks the page as resolved if its datetime has been reached, returning whether it wasn't
+    /// already.
+    fn resolve_if_reached(&mut self, now: DateTime<Utc>) -> bool {
+        if self.resolved || self.datetime > now {
+            return false;
+        }
+        self.resolved = true;
+        true
+    }
 }
 
 struct AppState {
     page_states: RwLock<HashMap<String, PageState>>,
-    tx: broadcast::Sender<i64>,
+    /// Channel of each page, broadcasting its updates to its websockets and event streams.
+    channels: HashMap<String, broadcast::Sender<PageUpdate>>,
+    config: Config,
+    db_pool: PgPool,
+    tasks_health: TasksHealth,
+    /// Cancelled when the server starts shutting down.
+    shutdown: CancellationToken,
+    /// Open websocket connections.
+    websockets: TaskTracker,
+    og_images: OgImageCache,
+    /// Limits clicks per client.
+    click_limiter: RateLimiter,
+    /// Results of `POST /api/{page}/click` by idempotency key.
+    click_results: IdempotencyCache<ClickResult>,
+    /// Events to send to webhooks.
+    webhooks: WebhookQueue,
+    /// Webhook deliveries that are still being sent or retried.
+    webhook_deliveries: TaskTracker,
+    /// Notified when events are scheduled or cancelled, so that the timed events task doesn't
+    /// sleep past them.
+    timed_events_changed: Notify,
 }
 
 impl AppState {
-    fn load(path: impl AsRef<std::path::Path>, tx: broadcast::Sender<i64>) -> Self {
+    fn load(
+        path: impl AsRef<std::path::Path>,
+        config: Config,
+        db_pool: PgPool,
+        webhooks: WebhookQueue,
+    ) -> Self {
         let file_contents = fs::read_to_string(path).unwrap();
         let page_states: HashMap<String, PageState> = serde_json::from_str(&file_contents).unwrap();
+        for (page_name, page_state) in &page_states {
+            if let Err(err) = page_state.increment.validate() {
+                panic!("invalid `increment` of page `{}`: {}", page_name, err);
+            }
+            if let Some(hope) = &page_state.hope
+                && let Err(err) = hope.validate()
+            {
+                panic!("invalid `hope` of page `{}`: {}", page_name, err);
+            }
+            for event in &page_state.events {
+                if let Err(err) = event.validate() {
+                    panic!(
+                        "invalid event `{}` of page `{}`: {}",
+                        event.name, page_name, err
+                    );
+                }
+            }
+        }
+        let channels = page_states
+            .keys()
+            .map(|page_name| {
+                let (tx, _rx) = broadcast::channel::<PageUpdate>(BROADCAST_CAPACITY);
+                (page_name.clone(), tx)
+            })
+            .collect();
 
         Self {
             page_states: RwLock::new(page_states),
-            tx,
+            channels,
+            click_limiter: RateLimiter::new(&config.rate_limit),
+            click_results: IdempotencyCache::new(Instant::now()),
+            webhooks,
+            webhook_deliveries: TaskTracker::new(),
+            timed_events_changed: Notify::new(),
+            config,
+            db_pool,
+            tasks_health: TasksHealth {
+                save: TaskHealth::new(SAVE_INTERVAL),
+                insert_time_series_data: TaskHealth::new(TIME_SERIES_INSERT_INTERVAL),
+                daily_summary: TaskHealth::new(DAILY_SUMMARY_INTERVAL),
+                timed_events: TaskHealth::new(TIMED_EVENTS_MAX_SLEEP),
+            },
+            shutdown: CancellationToken::new(),
+            websockets: TaskTracker::new(),
+            og_images: OgImageCache::default(),
         }
     }
 
-    async fn save(&self, path: impl AsRef<std::path::Path>) {
+    async fn save(&self, path: impl AsRef<std::path::Path>) -> io::Result<()> {
+        let start = Instant::now();
         let contents_serialized =
             serde_json::to_string_pretty(&*self.page_states.read().await).unwrap();
-        fs::write(path, contents_serialized).unwrap();
+        let result = fs::write(path, contents_serialized);
+
+        histogram!(SAVE_DURATION_SECONDS).record(start.elapsed());
+        if let Err(err) = &result {
+            counter!(SAVE_FAILURES_TOTAL).increment(1);
+            error!(%err, "Failed to save state");
+        }
+        result
     }
 
     async fn get_time_series_data_entries(&self) -> Vec<TimeSeriesDataEntry> {
@@ -93,10 +268,46 @@ impl AppState {
 
 #[tokio::main]
 async fn main() {
-    let (tx, _rx) = broadcast::channel::<i64>(20000);
-    let state = Arc::new(AppState::load(SAVE_FILE_PATH, tx));
+    let config = Config::load(CONFIG_FILE_PATH);
+    init_logging(&config.log);
+
+    match std::env::args().nth(1).as_deref() {
+        None => serve(config).await,
+        Some("migrate") => {
+            let applied = migrate().await.unwrap();
+            if applied.is_empty() {
+                info!("Database is up to date");
+            }
+            for (version, description) in applied {
+                info!(version, description, "Applied migration");
+            }
+        }
+        Some("retry-webhooks") => {
+            let db_pool = init_db().await.unwrap();
+            let (delivered, failed) = retry_dead_letters(&db_pool, &config.webhooks)
+                .await
+                .unwrap();
+            info!(delivered, failed, "Retried webhook dead letters");
+        }
+        Some(arg) => {
+            eprintln!("Unknown subcommand `{}`", arg);
+            eprintln!("Usage: update-countdown [migrate|retry-webhooks]");
+            std::process::exit(1);
+        }
+    }
+}
+
+async fn serve(config: Config) {
+    let metrics_handle = install_recorder();
 
     let db_pool = init_db().await.unwrap();
+    reconcile_time_series_policies(&db_pool, &config.time_series)
+        .await
+        .unwrap();
+
+    let (webhooks, webhook_rx) = WebhookQueue::new();
+    let state = Arc::new(AppState::load(SAVE_FILE_PATH, config, db_pool, webhooks));
+    let webhook_worker_task = tokio::spawn(webhook_worker(state.clone(), webhook_rx));
 
     let compression_layer = CompressionLayer::new()
         .br(true)
@@ -109,59 +320,166 @@ async fn main() {
         // TODO: Generalize these routes to work with any "game" page, so we can add more pages in
         // the future.
         .route("/battlebit", get(battlebit))
-        .route("/{game_name}/websocket", get(websocket_handler))
+        .route("/{page_name}/websocket", get(websocket_handler))
+        .route("/{page_name}/calendar.ics", get(calendar))
+        .route("/calendar.ics", get(calendar_all))
+        .route("/{page_name}/feed.atom", get(feed))
+        .route("/{page_name}/badge.svg", get(badge))
+        .route("/{page_name}/og.png", get(og_image))
+        .route("/{page_name}/embed", get(embed))
+        .route("/{page_name}/events", get(events))
+        .route("/api/{page_name}/click", post(click))
+        .route("/api/{page_name}", get(page))
+        .route("/api/{page_name}/stats", get(stats))
+        .route("/api/{page_name}/daily", get(daily_summaries))
+        .route("/api/{page_name}/remaining", get(remaining))
+        .route(
+            "/api/{page_name}/events",
+            get(list_events).post(create_event),
+        )
+        .route("/api/{page_name}/events/{event_name}", delete(cancel_event))
+        .route("/api/time", get(time))
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .route(
+            "/metrics",
+            get(move || std::future::ready(metrics_handle.render())),
+        )
         .with_state(state.clone())
         .nest_service("/assets", get_service(ServeDir::new("dist/assets")))
+        .layer(middleware::from_fn(track_http_requests))
         .layer(compression_layer)
-        .layer(TimeoutLayer::new(Duration::from_secs(10)));
+        .layer(TimeoutLayer::new(Duration::from_secs(10)))
+        // Layers added later wrap the ones before them, so the request ID is set before the span
+        // is created, and then copied to the response.
+        .layer(PropagateRequestIdLayer::new(HeaderName::from_static(
+            REQUEST_ID_HEADER,
+        )))
+        .layer(TraceLayer::new_for_http().make_span_with(make_request_span))
+        .layer(SetRequestIdLayer::new(
+            HeaderName::from_static(REQUEST_ID_HEADER),
+            MakeRequestUuid,
+        ));
 
     let listener = tokio::net::TcpListener::bind("0.0.0.0:7171").await.unwrap();
 
     let mut save_interval_task = tokio::spawn({
-        let mut save_interval = interval(Duration::from_secs(60 * 5));
-        // Do this because first tick completes immediately
-        save_interval.tick().await;
-        let state_cloned = state.clone();
+        let state = state.clone();
         async move {
-            loop {
-                save_interval.tick().await;
-                state_cloned.save(SAVE_FILE_PATH).await;
-                // TODO: use proper logging with a library
-                eprintln!("[{}] Saved state", Local::now().time().format("%H:%M:%S"));
-            }
+            supervise("save", &state.tasks_health.save, DEFAULT_BACKOFF, || {
+                save_task(state.clone())
+            })
+            .await
         }
     });
 
     let mut insert_time_series_data_task = tokio::spawn({
-        let mut interval = interval(Duration::from_secs(3));
-        // Do this because first tick completes immediately
-        interval.tick().await;
-        let state_cloned = state.clone();
+        let state = state.clone();
         async move {
-            loop {
-                interval.tick().await;
-                let data = state_cloned.get_time_series_data_entries().await;
-                insert_time_series_page_data(&db_pool, data).await.unwrap();
-            }
+            supervise(
+                "insert_time_series_data",
+                &state.tasks_health.insert_time_series_data,
+                DEFAULT_BACKOFF,
+                || insert_time_series_data_task(state.clone()),
+            )
+            .await
         }
     });
 
-    eprintln!("Listening on {}", &listener.local_addr().unwrap());
-    let serve_task = axum::serve(listener, app).with_graceful_shutdown(shutdown_signal());
+    let mut daily_summary_task = tokio::spawn({
+        let state = state.clone();
+        async move {
+            supervise(
+                "daily_summary",
+                &state.tasks_health.daily_summary,
+                DEFAULT_BACKOFF,
+                || daily_summary_task(state.clone()),
+            )
+            .await
+        }
+    });
 
-    tokio::select! {
-        _ = serve_task => {
-            save_interval_task.abort();
-            insert_time_series_data_task.abort();
+    let mut timed_events_task = tokio::spawn({
+        let state = state.clone();
+        async move {
+            supervise(
+                "timed_events",
+                &state.tasks_health.timed_events,
+                DEFAULT_BACKOFF,
+                || timed_events_task(state.clone()),
+            )
+            .await
         }
-        _ = &mut save_interval_task => insert_time_series_data_task.abort(),
-        _ = &mut insert_time_series_data_task => save_interval_task.abort(),
+    });
+
+    info!(address = %listener.local_addr().unwrap(), "Listening");
+    tokio::spawn({
+        let shutdown = state.shutdown.clone();
+        async move {
+            shutdown_signal().await;
+            shutdown.cancel();
+        }
+    });
+    // The client's address is needed for rate limiting.
+    let serve_task = axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(state.shutdown.clone().cancelled_owned());
+
+    // The background tasks only finish if they fail with a fatal error, which is already logged by
+    // their supervisor.
+    tokio::select! {
+        _ = serve_task => {}
+        _ = &mut save_interval_task => {}
+        _ = &mut insert_time_series_data_task => {}
+        _ = &mut daily_summary_task => {}
+        _ = &mut timed_events_task => {}
     }
+    save_interval_task.abort();
+    insert_time_series_data_task.abort();
+    daily_summary_task.abort();
+    timed_events_task.abort();
 
-    eprintln!("\nShutting down");
-    eprintln!("Saving state to `{}`", SAVE_FILE_PATH);
-    state.save(SAVE_FILE_PATH).await;
-    eprintln!("State saved successfully");
+    info!("Shutting down");
+    // Upgraded websockets aren't waited on by `with_graceful_shutdown`, so they are closed here.
+    // Cancelling makes them stop accepting clicks, and tell the client that the server is
+    // restarting before closing.
+    state.shutdown.cancel();
+    state.websockets.close();
+    if timeout(WEBSOCKET_DRAIN_TIMEOUT, state.websockets.wait())
+        .await
+        .is_err()
+    {
+        warn!(
+            remaining = state.websockets.len(),
+            "Timed out waiting for websockets to close"
+        );
+    }
+
+    // The worker stops once cancelled, dead-lettering the events still queued. Deliveries stop
+    // retrying, and are dead-lettered too.
+    let _ = webhook_worker_task.await;
+    state.webhook_deliveries.close();
+    if timeout(WEBHOOK_DRAIN_TIMEOUT, state.webhook_deliveries.wait())
+        .await
+        .is_err()
+    {
+        warn!(
+            remaining = state.webhook_deliveries.len(),
+            "Timed out waiting for webhook deliveries"
+        );
+    }
+
+    info!(path = SAVE_FILE_PATH, "Saving state");
+    state.save(SAVE_FILE_PATH).await.unwrap();
+    info!("State saved successfully");
+
+    let data = state.get_time_series_data_entries().await;
+    match insert_time_series_page_data(&state.db_pool, data).await {
+        Ok(()) => info!("Flushed time series data"),
+        Err(err) => error!(%err, "Failed to flush time series data"),
+    }
 }
 
 async fn shutdown_signal() {
@@ -0,0 +1,167 @@
+use std::sync::Arc;
+
+use chrono::{DateTime, Datelike, Utc};
+use tracing::{Instrument, error, info};
+
+use crate::AppState;
+use crate::db::insert_milestone;
+use crate::webhooks::{WebhookEvent, WebhookPayload};
+
+/// Click counts that are milestones once reached.
+const CLICK_COUNT_MILESTONES: [i64; 2] = [10_000, 100_000];
+
+/// A notable event of a page, shown in its feed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Milestone {
+    /// The click count reached this many clicks.
+    ClickCount(i64),
+    /// The datetime was pushed forward into this year.
+    NewYear(i32),
+    /// The datetime was reached, so the countdown ran out.
+    Resolved,
+}
+
+impl Milestone {
+    /// Milestones reached by clicks that changed a page's click count from `click_count_before` to
+    /// `click_count_after`, and its datetime to `datetime_after`. `latest_year_before` is the
+    /// latest year the datetime had been pushed into, which is only reached again once the
+    /// datetime goes past it, even if hope mode pulled the datetime back in the meantime.
+    pub fn after_clicks(
+        click_count_before: i64,
+        click_count_after: i64,
+        latest_year_before: i32,
+        datetime_after: DateTime<Utc>,
+    ) -> Vec<Milestone> {
+        let click_counts = CLICK_COUNT_MILESTONES
+            .into_iter()
+            .filter(|&count| click_count_before < count && count <= click_count_after)
+            .map(Milestone::ClickCount);
+        let years = (latest_year_before + 1..=datetime_after.year()).map(Milestone::NewYear);
+
+        click_counts.chain(years).collect()
+    }
+
+    /// Name of the kind of milestone, as stored in the database.
+    pub fn kind(self) -> &'static str {
+        match self {
+            Milestone::ClickCount(_) => "click_count",
+            Milestone::NewYear(_) => "new_year",
+            Milestone::Resolved => "resolved",
+        }
+    }
+
+    /// Value stored in the database along with the kind.
+    pub fn value(self) -> Option<i64> {
+        match self {
+            Milestone::ClickCount(count) => Some(count),
+            Milestone::NewYear(year) => Some(year as i64),
+            Milestone::Resolved => None,
+        }
+    }
+
+    /// Inverse of `kind` and `value`. Returns `None` if they aren't valid.
+    pub fn from_kind(kind: &str, value: Option<i64>) -> Option<Self> {
+        match (kind, value) {
+            ("click_count", Some(count)) => Some(Milestone::ClickCount(count)),
+            ("new_year", Some(year)) => Some(Milestone::NewYear(year.try_into().ok()?)),
+            ("resolved", None) => Some(Milestone::Resolved),
+            _ => None,
+        }
+    }
+
+    pub fn title(self, page_title: &str) -> String {
+        match self {
+            Milestone::ClickCount(count) => format!("{} reached {} clicks", page_title, count),
+            Milestone::NewYear(year) => format!("{} was pushed into {}", page_title, year),
+            Milestone::Resolved => format!("{} countdown ran out", page_title),
+        }
+    }
+}
+
+/// A milestone stored in the database.
+pub struct MilestoneEntry {
+    pub id: i64,
+    pub timestamp: DateTime<Utc>,
+    pub milestone: Milestone,
+    /// The page's datetime at the time.
+    pub datetime: DateTime<Utc>,
+}
+
+/// Stores a milestone and sends it to webhooks in the background, so that the caller doesn't have
+/// to wait for the database while holding the page's lock.
+pub fn record_milestone(
+    state: &Arc<AppState>,
+    page_name: &str,
+    milestone: Milestone,
+    datetime: DateTime<Utc>,
+) {
+    info!(page_name, ?milestone, "Milestone reached");
+    let state = state.clone();
+    let page_name = page_name.to_string();
+    let timestamp = Utc::now();
+    tokio::spawn(
+        async move {
+            if let Err(err) =
+                insert_milestone(&state.db_pool, &page_name, milestone, datetime, timestamp).await
+            {
+                error!(%err, ?milestone, "Failed to store milestone");
+            }
+
+            let title = state.page_states.read().await[&page_name]
+                .title(&page_name)
+                .to_string();
+            let event = WebhookEvent::from_milestone(milestone, datetime);
+            state.webhooks.push(WebhookPayload {
+                message: event.message(&title),
+                title,
+                page: page_name,
+                timestamp,
+                event,
+            });
+        }
+        .in_current_span(),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use crate::milestones::Milestone;
+
+    #[test]
+    fn after_clicks() {
+        let dec_31 = Utc.with_ymd_and_hms(2026, 12, 31, 23, 50, 0).unwrap();
+        let jan_1 = Utc.with_ymd_and_hms(2027, 1, 1, 0, 20, 0).unwrap();
+
+        assert_eq!(Milestone::after_clicks(5, 6, 2026, dec_31), []);
+        assert_eq!(
+            Milestone::after_clicks(9_999, 10_000, 2026, dec_31),
+            [Milestone::ClickCount(10_000)]
+        );
+        assert_eq!(Milestone::after_clicks(10_000, 10_001, 2026, dec_31), []);
+        assert_eq!(
+            Milestone::after_clicks(99_999, 100_000, 2026, jan_1),
+            [Milestone::ClickCount(100_000), Milestone::NewYear(2027)]
+        );
+
+        // Pulled back into 2026 by hope mode, then pushed into 2027 again.
+        assert_eq!(Milestone::after_clicks(7, 8, 2027, dec_31), []);
+        assert_eq!(Milestone::after_clicks(8, 9, 2027, jan_1), []);
+    }
+
+    #[test]
+    fn kind_roundtrip() {
+        for milestone in [
+            Milestone::ClickCount(10_000),
+            Milestone::NewYear(2027),
+            Milestone::Resolved,
+        ] {
+            assert_eq!(
+                Milestone::from_kind(milestone.kind(), milestone.value()),
+                Some(milestone)
+            );
+        }
+        assert_eq!(Milestone::from_kind("resolved", Some(1)), None);
+    }
+}
@@ -0,0 +1,212 @@
+use std::sync::Mutex;
+use std::time::Duration;
+
+use ab_glyph::{Font, FontRef, PxScale, ScaleFont};
+use axum::body::Bytes;
+use chrono::{DateTime, Utc};
+use hashbrown::HashMap;
+use tokio::task::spawn_blocking;
+
+pub const WIDTH: u32 = 1200;
+pub const HEIGHT: u32 = 630;
+/// How long a rendered image is reused for. It's reused even if the page's datetime changes, since
+/// clicks change it all the time, so the remaining time in it can be off by this long plus
+/// whatever was clicked in the meantime.
+pub const CACHE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Colors of the dark theme in `style.css`.
+const BACKGROUND: [u8; 3] = [18, 18, 22];
+const FOREGROUND: [u8; 3] = [235, 235, 255];
+const DIM_FOREGROUND: [u8; 3] = [120, 120, 145];
+
+/// Horizontal space left empty on each side of the text.
+const MARGIN: f32 = 80.0;
+
+/// Renders the Open Graph image of a page, showing its title and the time remaining, as a PNG.
+pub fn render_og_image(title: &str, caption: &str, remaining: &str) -> Vec<u8> {
+    let regular = FontRef::try_from_slice(notosans::REGULAR_TTF).unwrap();
+    let bold = FontRef::try_from_slice(notosans::BOLD_TTF).unwrap();
+
+    let mut image = Image::new(WIDTH, HEIGHT, BACKGROUND);
+    image.draw_text_centered(&bold, title, 80.0, 230.0, FOREGROUND);
+    image.draw_text_centered(&regular, caption, 44.0, 310.0, DIM_FOREGROUND);
+    image.draw_text_centered(&bold, remaining, 110.0, 450.0, FOREGROUND);
+    image.draw_text_centered(&regular, "Update Countdown", 32.0, 580.0, DIM_FOREGROUND);
+    image.encode_png()
+}
+
+/// RGB image.
+struct Image {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl Image {
+    fn new(width: u32, height: u32, background: [u8; 3]) -> Self {
+        Self {
+            width,
+            height,
+            pixels: background.repeat((width * height) as usize),
+        }
+    }
+
+    /// Draws a line of text horizontally centered, with its baseline at `baseline_y`. The text is
+    /// shrunk if it doesn't fit between the margins.
+    fn draw_text_centered(
+        &mut self,
+        font: &FontRef,
+        text: &str,
+        size: f32,
+        baseline_y: f32,
+        color: [u8; 3],
+    ) {
+        let max_width = self.width as f32 - MARGIN * 2.0;
+        let mut scale = PxScale::from(size);
+        let mut width = text_width(font, scale, text);
+        if width > max_width {
+            scale = PxScale::from(size * max_width / width);
+            width = max_width;
+        }
+
+        let scaled_font = font.as_scaled(scale);
+        let mut x = (self.width as f32 - width) / 2.0;
+        let mut previous = None;
+        for c in text.chars() {
+            let glyph_id = scaled_font.glyph_id(c);
+            if let Some(previous) = previous {
+                x += scaled_font.kern(previous, glyph_id);
+            }
+            previous = Some(glyph_id);
+
+            let glyph = glyph_id.with_scale_and_position(scale, ab_glyph::point(x, baseline_y));
+            x += scaled_font.h_advance(glyph_id);
+            let Some(outlined) = font.outline_glyph(glyph) else {
+                // e.g. spaces
+                continue;
+            };
+
+            let bounds = outlined.px_bounds();
+            outlined.draw(|glyph_x, glyph_y, coverage| {
+                let x = bounds.min.x as i32 + glyph_x as i32;
+                let y = bounds.min.y as i32 + glyph_y as i32;
+                self.blend(x, y, color, coverage);
+            });
+        }
+    }
+
+    fn blend(&mut self, x: i32, y: i32, color: [u8; 3], alpha: f32) {
+        if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
+            return;
+        }
+        let i = (y as usize * self.width as usize + x as usize) * 3;
+        let alpha = alpha.clamp(0.0, 1.0);
+        for (pixel, channel) in self.pixels[i..i + 3].iter_mut().zip(color) {
+            *pixel = (*pixel as f32 * (1.0 - alpha) + channel as f32 * alpha).round() as u8;
+        }
+    }
+
+    fn encode_png(&self) -> Vec<u8> {
+        let mut png = Vec::new();
+        let mut encoder = png::Encoder::new(&mut png, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(&self.pixels).unwrap();
+        writer.finish().unwrap();
+        png
+    }
+}
+
+fn text_width(font: &FontRef, scale: PxScale, text: &str) -> f32 {
+    let scaled_font = font.as_scaled(scale);
+    let mut width = 0.0;
+    let mut previous = None;
+    for c in text.chars() {
+        let glyph_id = scaled_font.glyph_id(c);
+        if let Some(previous) = previous {
+            width += scaled_font.kern(previous, glyph_id);
+        }
+        width += scaled_font.h_advance(glyph_id);
+        previous = Some(glyph_id);
+    }
+    width
+}
+
+struct CachedOgImage {
+    rendered_at: DateTime<Utc>,
+    png: Bytes,
+}
+
+/// Rendered Open Graph images of each page, since link previews of a popular link can be requested
+/// a lot at once.
+#[derive(Default)]
+pub struct OgImageCache {
+    images: Mutex<HashMap<String, CachedOgImage>>,
+}
+
+impl OgImageCache {
+    /// Returns the cached image of the page if it was rendered less than `CACHE_INTERVAL` ago,
+    /// otherwise renders it with `render` on a blocking thread, since drawing
+    /// the text takes a while.
+    pub async fn get_or_render(
+        &self,
+        page_name: &str,
+        now: DateTime<Utc>,
+        render: impl FnOnce() -> Vec<u8> + Send + 'static,
+    ) -> Bytes {
+        if let Some(cached) = self.images.lock().unwrap().get(page_name)
+            && (now - cached.rendered_at)
+                .to_std()
+                .is_ok_and(|age| age < CACHE_INTERVAL)
+        {
+            return cached.png.clone();
+        }
+
+        // Rendered without holding the lock, so that other pages aren't blocked in the meantime.
+        let png = Bytes::from(spawn_blocking(render).await.unwrap());
+        self.images.lock().unwrap().insert(
+            page_name.to_string(),
+            CachedOgImage {
+                rendered_at: now,
+                png: png.clone(),
+            },
+        );
+        png
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeDelta, Utc};
+
+    use crate::og_image::{HEIGHT, OgImageCache, WIDTH, render_og_image};
+
+    #[test]
+    fn png() {
+        let png = render_og_image("BattleBit Remastered", "updates in", "812d 3h 0m 12s");
+        let decoder = png::Decoder::new(std::io::Cursor::new(png));
+        let info = decoder.read_info().unwrap();
+        assert_eq!(info.info().width, WIDTH);
+        assert_eq!(info.info().height, HEIGHT);
+    }
+
+    #[tokio::test]
+    async fn cache() {
+        let cache = OgImageCache::default();
+        let now = Utc::now();
+
+        let first = cache.get_or_render("battlebit", now, || vec![1]).await;
+        let cached = cache
+            .get_or_render("battlebit", now + TimeDelta::seconds(30), || vec![2])
+            .await;
+        assert_eq!(first, cached);
+        let other = cache.get_or_render("other", now, || vec![3]).await;
+        assert_eq!(other, [3].as_slice());
+
+        let expired = cache
+            .get_or_render("battlebit", now + TimeDelta::minutes(2), || vec![4])
+            .await;
+        assert_eq!(expired, [4].as_slice());
+    }
+}
@@ -0,0 +1,157 @@
+use axum::extract::ws::{CloseFrame, Message, close_code};
+use serde::{Deserialize, Serialize};
+
+use crate::increment::IncrementDistribution;
+
+/// Control messages sent to websocket clients as JSON text frames, e.g.
+/// `{"type":"restarting"}`. `PageUpdate`s are sent as binary frames instead, since they are sent a
+/// lot more often.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    /// The server is shutting down, and the connection will be closed right after.
+    Restarting,
+    /// Reply to `ClientMessage::TimeSync`. Times are Unix timestamps in milliseconds.
+    ///
+    /// The client can estimate its clock's offset from the server as
+    /// `server_time + round_trip / 2 - now`, where `round_trip` is `now - client_time`.
+    ServerTime { client_time: i64, server_time: i64 },
+    /// A timed event started, multiplying how far clicks push the datetime until `end`, a Unix
+    /// timestamp in milliseconds, and replacing the page's increment distribution if `increment`
+    /// is set. Also sent on connect for events that are already on.
+    EventStarted {
+        name: String,
+        end: i64,
+        multiplier: f64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        increment: Option<IncrementDistribution>,
+    },
+    /// A timed event ended.
+    EventEnded { name: String },
+}
+
+impl ServerMessage {
+    pub fn to_message(&self) -> Message {
+        Message::Text(serde_json::to_string(self).unwrap().into())
+    }
+}
+
+/// Update broadcast to everyone viewing a page. Times are Unix timestamps in milliseconds.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PageUpdate {
+    /// The page's current datetime, e.g. when someone connects.
+    Datetime(i64),
+    /// Someone clicked, moving the datetime by `delta`, which is negative if the click subtracted
+    /// time.
+    Click {
+        datetime: i64,
+        delta: i64,
+    },
+    UserCount(i32),
+    /// Control message for everyone, e.g. a timed event starting.
+    Announcement(ServerMessage),
+}
+
+impl PageUpdate {
+    /// Encodes the update as a binary websocket frame: a big-endian `i64` that is either the
+    /// datetime or the negated user count. Clicks are followed by another `i64` with the delta, so
+    /// clients that only read the first one still get the datetime. Announcements are sent as JSON
+    /// text frames instead.
+    pub fn to_message(&self) -> Message {
+        let bytes = match *self {
+            PageUpdate::Announcement(ref message) => return message.to_message(),
+            PageUpdate::Datetime(datetime) => datetime.to_be_bytes().to_vec(),
+            PageUpdate::Click { datetime, delta } => {
+                [datetime.to_be_bytes(), delta.to_be_bytes()].concat()
+            }
+            PageUpdate::UserCount(user_count) => (-(user_count as i64)).to_be_bytes().to_vec(),
+        };
+        Message::Binary(bytes.into())
+    }
+}
+
+/// Control messages received from websocket clients as JSON text frames. Clicks are sent as empty
+/// binary frames instead.
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    /// Asks for the server's current time, to correct for the client's clock being off.
+    TimeSync {
+        /// The client's current time, as a Unix timestamp in milliseconds.
+        client_time: i64,
+        /// How far ahead of the server's clock the client's clock was, in milliseconds, as
+        /// estimated from the previous sync. Only used for metrics.
+        #[serde(default)]
+        skew: Option<i64>,
+    },
+}
+
+/// Close frame sent to clients when the server shuts down, so they know to reconnect later.
+pub fn restarting_close_frame() -> Message {
+    Message::Close(Some(CloseFrame {
+        code: close_code::RESTART,
+        reason: "Server restarting".into(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::extract::ws::Message;
+
+    use crate::increment::IncrementDistribution;
+    use crate::protocol::{ClientMessage, PageUpdate, ServerMessage};
+
+    #[test]
+    fn time_sync() {
+        let msg = r#"{"type":"time_sync","client_time":1760000000000,"skew":-250}"#;
+        let ClientMessage::TimeSync { client_time, skew } = serde_json::from_str(msg).unwrap();
+        assert_eq!(client_time, 1760000000000);
+        assert_eq!(skew, Some(-250));
+
+        let reply = ServerMessage::ServerTime {
+            client_time,
+            server_time: 1760000000100,
+        };
+        assert_eq!(
+            serde_json::to_string(&reply).unwrap(),
+            r#"{"type":"server_time","client_time":1760000000000,"server_time":1760000000100}"#
+        );
+    }
+
+    #[test]
+    fn page_update_frames() {
+        let Message::Binary(datetime) = PageUpdate::Datetime(1760000000000).to_message() else {
+            panic!("expected a binary frame");
+        };
+        assert_eq!(datetime[..], 1760000000000i64.to_be_bytes());
+
+        let Message::Binary(user_count) = PageUpdate::UserCount(3).to_message() else {
+            panic!("expected a binary frame");
+        };
+        assert_eq!(user_count[..], (-3i64).to_be_bytes());
+
+        let click = PageUpdate::Click {
+            datetime: 1760000000000,
+            delta: -1500,
+        };
+        let Message::Binary(click) = click.to_message() else {
+            panic!("expected a binary frame");
+        };
+        assert_eq!(click[..8], 1760000000000i64.to_be_bytes());
+        assert_eq!(click[8..], (-1500i64).to_be_bytes());
+    }
+
+    #[test]
+    fn event_started() {
+        let started = ServerMessage::EventStarted {
+            name: "Anniversary".to_string(),
+            end: 1760000000000,
+            multiplier: 1.0,
+            increment: Some(IncrementDistribution::Fixed { seconds: 60.0 }),
+        };
+        assert_eq!(
+            serde_json::to_string(&started).unwrap(),
+            r#"{"type":"event_started","name":"Anniversary","end":1760000000000,"multiplier":1.0,"increment":{"type":"fixed","seconds":60.0}}"#
+        );
+    }
+}
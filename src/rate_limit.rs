@@ -0,0 +1,151 @@
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use hashbrown::HashMap;
+
+use crate::config::RateLimitConfig;
+
+/// Number of clients tracked before buckets that have refilled are first dropped.
+const PRUNE_THRESHOLD: usize = 10_000;
+
+/// Token bucket rate limiter per client IP, shared by websocket and HTTP clicks so that neither
+/// can be used to get around the other.
+pub struct RateLimiter {
+    /// Tokens added per second.
+    rate: f64,
+    /// Maximum number of tokens, i.e. how many clicks can be made at once.
+    burst: f64,
+    /// How long an empty bucket takes to fill up, after which it can be dropped.
+    refill_time: Duration,
+    buckets: Mutex<Buckets>,
+}
+
+struct Buckets {
+    clients: HashMap<IpAddr, Bucket>,
+    /// Number of clients at which buckets are pruned next. Raised after each pruning, so that
+    /// pruning doesn't run on every click while most clients are still active.
+    prune_at: usize,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    /// The config must be valid.
+    pub fn new(config: &RateLimitConfig) -> Self {
+        let rate = config.clicks_per_second;
+        let burst = config.burst as f64;
+        Self {
+            rate,
+            burst,
+            refill_time: Duration::from_secs_f64(burst / rate),
+            buckets: Mutex::new(Buckets {
+                clients: HashMap::new(),
+                prune_at: PRUNE_THRESHOLD,
+            }),
+        }
+    }
+
+    /// Takes a token from the client's bucket, or returns how long until one is available.
+    pub fn check(&self, client: IpAddr, now: Instant) -> Result<(), Duration> {
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.clients.len() >= buckets.prune_at {
+            buckets
+                .clients
+                .retain(|_, bucket| now.duration_since(bucket.updated) < self.refill_time);
+            buckets.prune_at = (buckets.clients.len() * 2).max(PRUNE_THRESHOLD);
+        }
+
+        let bucket = buckets.clients.entry(client).or_insert(Bucket {
+            tokens: self.burst,
+            updated: now,
+        });
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.rate).min(self.burst);
+        bucket.updated = now;
+
+        if bucket.tokens < 1.0 {
+            return Err(Duration::from_secs_f64((1.0 - bucket.tokens) / self.rate));
+        }
+        bucket.tokens -= 1.0;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::time::{Duration, Instant};
+
+    use crate::config::RateLimitConfig;
+    use crate::rate_limit::{PRUNE_THRESHOLD, RateLimiter};
+
+    #[test]
+    fn burst_then_refill() {
+        let limiter = RateLimiter::new(&RateLimitConfig {
+            clicks_per_second: 2.0,
+            burst: 3,
+            ..Default::default()
+        });
+        let client = IpAddr::V4(Ipv4Addr::LOCALHOST);
+        let other = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        let now = Instant::now();
+
+        for _ in 0..3 {
+            assert!(limiter.check(client, now).is_ok());
+        }
+        assert_eq!(limiter.check(client, now), Err(Duration::from_millis(500)));
+        assert!(limiter.check(other, now).is_ok());
+
+        let later = now + Duration::from_millis(500);
+        assert!(limiter.check(client, later).is_ok());
+        assert!(limiter.check(client, later).is_err());
+    }
+
+    #[test]
+    fn prune() {
+        let limiter = RateLimiter::new(&RateLimitConfig::default());
+        let client = |i: usize| IpAddr::V4(Ipv4Addr::from_bits(i as u32));
+        let now = Instant::now();
+
+        for i in 0..=PRUNE_THRESHOLD {
+            assert!(limiter.check(client(i), now).is_ok());
+        }
+        // Every client is still active, so none are dropped, and pruning waits for the number of
+        // clients to double.
+        let buckets = limiter.buckets.lock().unwrap();
+        assert_eq!(buckets.clients.len(), PRUNE_THRESHOLD + 1);
+        assert_eq!(buckets.prune_at, PRUNE_THRESHOLD * 2);
+        drop(buckets);
+
+        let later = now + limiter.refill_time;
+        for i in PRUNE_THRESHOLD + 1..=PRUNE_THRESHOLD * 2 {
+            assert!(limiter.check(client(i), later).is_ok());
+        }
+        // The first clients have refilled by now.
+        assert_eq!(
+            limiter.buckets.lock().unwrap().clients.len(),
+            PRUNE_THRESHOLD
+        );
+    }
+
+    #[test]
+    fn invalid_config() {
+        for clicks_per_second in [0.0, -1.0, 1e-300, f64::INFINITY, f64::NAN] {
+            let config = RateLimitConfig {
+                clicks_per_second,
+                ..Default::default()
+            };
+            assert!(config.validate().is_err(), "{}", clicks_per_second);
+        }
+        let no_burst = RateLimitConfig {
+            burst: 0,
+            ..Default::default()
+        };
+        assert!(no_burst.validate().is_err());
+        assert!(RateLimitConfig::default().validate().is_ok());
+    }
+}
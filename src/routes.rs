@@ -1,31 +1,85 @@
-use std::ops::Range;
-use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU8, Ordering};
-use std::{sync::Arc, time::Duration};
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::ops::RangeInclusive;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use askama::Template;
 use axum::extract::ws::{Message, WebSocket};
-use axum::extract::{State, WebSocketUpgrade};
-use axum::response::{Html, IntoResponse, Redirect};
-use axum::{body::Bytes, http::StatusCode};
-
-use chrono::{TimeDelta, Utc};
-use futures::{SinkExt, stream::StreamExt};
-use rand::distr::Distribution;
-use rand::{SeedableRng, distr::Uniform, rngs::SmallRng};
+use axum::extract::{ConnectInfo, Path, Query, State, WebSocketUpgrade};
+use axum::http::{
+    HeaderMap, StatusCode,
+    header::{
+        ACCEPT_LANGUAGE, AUTHORIZATION, CACHE_CONTROL, CONTENT_TYPE, ETAG, IF_NONE_MATCH,
+        RETRY_AFTER, VARY, WWW_AUTHENTICATE,
+    },
+};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{Html, IntoResponse, Json, Redirect, Response};
+
+use chrono::{DateTime, Datelike, NaiveDateTime, TimeDelta, Utc};
+use futures::{SinkExt, stream, stream::StreamExt};
+use metrics::{counter, gauge, histogram};
+use rand::{Rng, SeedableRng, rngs::SmallRng};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc;
 use tokio::time::interval;
+use tracing::{Instrument, debug, error, info, info_span};
 
-use crate::datetime::datetime_difference;
-use crate::AppState;
+use crate::badge::{BadgeStyle, parse_color, render_badge};
+use crate::datetime::{
+    DurationParts, DurationStyle, Locale, Precision, SignedDuration, duration_parts,
+    format_duration, format_signed_duration,
+};
+use crate::db::{Resolution, query_daily_summaries, query_milestones, query_time_series_stats};
+use crate::feed::{FeedEntry, atom_feed};
+use crate::health::{ComponentStatus, check_database, check_writable};
+use crate::ical::{self, CalendarEvent};
+use crate::idempotency::MAX_IDEMPOTENCY_KEY_LEN;
+use crate::milestones::{Milestone, record_milestone};
+use crate::og_image::{self, render_og_image};
+use crate::protocol::{ClientMessage, PageUpdate, ServerMessage, restarting_close_frame};
+use crate::telemetry::{
+    BROADCAST_LAG_EVENTS_TOTAL, BROADCAST_LAGGED_MESSAGES_TOTAL, CLICKS_TOTAL,
+    CLIENT_CLOCK_SKEW_SECONDS, EVENT_STREAM_CONNECTIONS, WEBSOCKET_CONNECTIONS, request_id,
+};
+use crate::timed_events::{TimedEvent, sample_increment};
+use crate::webhooks::daily_summary_message;
+use crate::{AppState, PageState, SAVE_FILE_PATH};
 
-const SECS_INCREMENT_RANGE: Range<i64> = (25 * 60)..(35 * 60);
 const MAX_MESSAGES_PER_INTERVAL: u8 = 10;
 
 #[derive(Template)]
 #[template(path = "countdown.html")]
 struct CountdownTemplate {
     title: String,
+    /// Unix timestamp in milliseconds.
     datetime: i64,
     datetime_duration: String,
+    /// Whether the datetime has already passed.
+    overdue: bool,
+    /// Absolute URL of the page's Open Graph image, if `public_url` is set.
+    og_image: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "embed.html")]
+struct EmbedTemplate {
+    title: String,
+    page_name: String,
+    /// Unix timestamp in milliseconds.
+    datetime: i64,
+    theme: Theme,
+    /// In pixels.
+    font_size: u16,
+    show_button: bool,
 }
 
 pub async fn root() -> Redirect {
@@ -34,61 +88,130 @@ pub async fn root() -> Redirect {
 
 pub async fn websocket_handler(
     ws: WebSocketUpgrade,
+    Path(page_name): Path<String>,
     State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
 ) -> impl IntoResponse {
+    if !state.channels.contains_key(&page_name) {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+
+    // The upgraded connection outlives the request's span, so it gets its own span with the same
+    // request ID.
+    let span = info_span!(
+        "websocket",
+        page = page_name,
+        request_id = request_id(&headers),
+    );
+    let client_ip = client_ip(&state, &headers, addr);
+    // Tracked so that shutdown can wait for connections to close.
+    let websockets = state.websockets.clone();
     ws.max_message_size((i64::BITS * 2).try_into().unwrap())
-        .on_upgrade(|socket| websocket(socket, state))
+        .on_upgrade(move |socket| {
+            websockets.track_future(websocket(socket, state, page_name, client_ip).instrument(span))
+        })
+        .into_response()
 }
 
-async fn websocket(stream: WebSocket, state: Arc<AppState>) {
+async fn websocket(stream: WebSocket, state: Arc<AppState>, page_name: String, client_ip: IpAddr) {
+    let connected_at = Instant::now();
+    info!("Websocket connected");
     let (mut sender, mut reciever) = stream.split();
 
     let num_messages_recieved = Arc::new(AtomicU8::new(0));
 
     let has_incremented_user_count = Arc::new(AtomicBool::new(false));
+    // Replies to client messages, sent by the send task.
+    let (reply_tx, mut reply_rx) = mpsc::channel::<ServerMessage>(8);
     let read_lock = state.page_states.read().await;
-    let page_state = read_lock.get("battlebit").unwrap();
-    let last_timestamp_recieved = Arc::new(AtomicI64::new(page_state.datetime.timestamp()));
+    let page_state = read_lock.get(&page_name).unwrap();
+    // Latest update held back by the send task, sent once the interval finishes.
+    let mut last_update = PageUpdate::Datetime(page_state.datetime.timestamp_millis());
     drop(read_lock);
 
     let mut recieve_task = tokio::spawn({
         let state_cloned = state.clone();
-        let tx = state_cloned.tx.clone();
+        let tx = state_cloned.channels[&page_name].clone();
+        let page_name = page_name.clone();
         let mut rng = SmallRng::from_os_rng();
-        let secs_range = Uniform::try_from(SECS_INCREMENT_RANGE).unwrap();
         let incremented_user_count = has_incremented_user_count.clone();
         async move {
             let mut write_lock = state_cloned.page_states.write().await;
-            let page_state = write_lock.get_mut("battlebit").unwrap();
+            let page_state = write_lock.get_mut(&page_name).unwrap();
             page_state.user_count += 1;
             incremented_user_count.store(true, Ordering::SeqCst);
+            gauge!(WEBSOCKET_CONNECTIONS, "page" => page_name.clone()).increment(1);
 
-            tx.send(page_state.datetime.timestamp()).unwrap();
+            // Sending only fails if nobody is subscribed, in which case there's nobody to tell.
+            let _ = tx.send(PageUpdate::Datetime(page_state.datetime.timestamp_millis()));
+            if page_state.resolve_if_reached(Utc::now()) {
+                record_milestone(
+                    &state_cloned,
+                    &page_name,
+                    Milestone::Resolved,
+                    page_state.datetime,
+                );
+            }
 
             // Send incremented user count
-            tx.send(page_state.user_count as i64 * -1).unwrap();
+            let _ = tx.send(PageUpdate::UserCount(page_state.user_count));
+            let active_events = active_event_messages(page_state, Utc::now());
             drop(write_lock);
 
-            while let Some(Ok(Message::Binary(msg))) = reciever.next().await {
-                let mut write_lock = state_cloned.page_states.write().await;
-                let page_state = write_lock.get_mut("battlebit").unwrap();
-                if !msg.is_empty() {
+            for message in active_events {
+                let _ = reply_tx.send(message).await;
+            }
+
+            while let Some(Ok(msg)) = reciever.next().await {
+                let msg = match msg {
+                    Message::Binary(msg) => msg,
+                    Message::Text(text) => {
+                        match serde_json::from_str::<ClientMessage>(&text) {
+                            Ok(ClientMessage::TimeSync { client_time, skew }) => {
+                                if let Some(skew) = skew {
+                                    histogram!(CLIENT_CLOCK_SKEW_SECONDS, "page" => page_name.clone())
+                                        .record(skew.unsigned_abs() as f64 / 1000.0);
+                                }
+                                let server_time = Utc::now().timestamp_millis();
+                                let reply = ServerMessage::ServerTime {
+                                    client_time,
+                                    server_time,
+                                };
+                                if reply_tx.send(reply).await.is_err() {
+                                    break;
+                                }
+                            }
+                            Err(err) => debug!(%err, "Ignoring invalid websocket message"),
+                        }
+                        continue;
+                    }
+                    _ => break,
+                };
+
+                // Stop accepting clicks once shutting down, so that the saved state is final.
+                if state_cloned.shutdown.is_cancelled() {
                     continue;
                 }
 
-                page_state.click_count += 1;
+                if !msg.is_empty() {
+                    continue;
+                }
+                // Clicks over the limit are dropped silently, since the client doesn't wait for a
+                // reply anyway.
+                if state_cloned.click_limiter.check(client_ip, Instant::now()).is_err() {
+                    continue;
+                }
 
-                let secs = secs_range.sample(&mut rng);
-                page_state.datetime = page_state
-                    .datetime
-                    .checked_add_signed(TimeDelta::seconds(secs))
-                    .unwrap();
-                tx.send(page_state.datetime.timestamp()).unwrap();
+                let mut write_lock = state_cloned.page_states.write().await;
+                let page_state = write_lock.get_mut(&page_name).unwrap();
+                apply_click(&state_cloned, &page_name, page_state, &mut rng);
             }
         }
+        .in_current_span()
     });
 
-    let mut rx = state.tx.subscribe();
+    let mut rx = state.channels[&page_name].subscribe();
 
     let mut send_task = tokio::spawn({
         // For each user, limit the amount of messages per interval to a specified amount. If the
@@ -105,12 +228,42 @@ async fn websocket(stream: WebSocket, state: Arc<AppState>) {
         // TODO: It would be better if, instead, we would control how many messages will be
         // recieved by each user per interval.
         let mut interval = interval(Duration::from_millis(500));
+        let state_cloned = state.clone();
         async move {
             // TODO: would stream merging be a better choice instead of `select!` inside `loop`?
             // (as recommened by the tokio docs)
             loop {
                 tokio::select! {
-                    Ok(timestamp_msg) = rx.recv() => {
+                    _ = state_cloned.shutdown.cancelled() => {
+                        // Errors are ignored since the connection is closed either way.
+                        let _ = sender.send(ServerMessage::Restarting.to_message()).await;
+                        let _ = sender.send(restarting_close_frame()).await;
+                        break;
+                    },
+                    Some(reply) = reply_rx.recv() => {
+                        if sender.send(reply.to_message()).await.is_err() {
+                            break;
+                        }
+                    },
+                    recieved = rx.recv() => {
+                        let update = match recieved {
+                            Ok(update) => update,
+                            Err(RecvError::Lagged(num_skipped)) => {
+                                counter!(BROADCAST_LAG_EVENTS_TOTAL).increment(1);
+                                counter!(BROADCAST_LAGGED_MESSAGES_TOTAL).increment(num_skipped);
+                                continue;
+                            }
+                            Err(RecvError::Closed) => break,
+                        };
+
+                        // Announcements are rare, and can't be replaced by a later update.
+                        if let PageUpdate::Announcement(_) = update {
+                            if sender.send(update.to_message()).await.is_err() {
+                                break;
+                            }
+                            continue;
+                        }
+
                         // Fetch, then increment, then also increment fetched value so that it
                         // matches the incremented value. Basically `add_fetch()`.
                         let num_messages = num_messages_recieved
@@ -118,15 +271,11 @@ async fn websocket(stream: WebSocket, state: Arc<AppState>) {
                             .saturating_add(1);
 
                         if num_messages <= MAX_MESSAGES_PER_INTERVAL {
-                            if sender
-                                .send(Message::Binary(Bytes::from_iter(timestamp_msg.to_be_bytes())))
-                                .await
-                                .is_err()
-                            {
+                            if sender.send(update.to_message()).await.is_err() {
                                 break;
                             }
                         } else {
-                            last_timestamp_recieved.store(timestamp_msg, Ordering::Relaxed);
+                            last_update = update;
                         }
                     },
                     // Interval finishes
@@ -136,19 +285,14 @@ async fn websocket(stream: WebSocket, state: Arc<AppState>) {
                         {
                             continue;
                         }
-                        if sender
-                            .send(Message::Binary(
-                                Bytes::from_iter(last_timestamp_recieved.load(Ordering::Relaxed).to_be_bytes()),
-                            ))
-                            .await
-                            .is_err()
-                        {
+                        if sender.send(last_update.to_message()).await.is_err() {
                             break;
                         }
                     }
                 }
             }
         }
+        .in_current_span()
     });
 
     // Abort the other task if one of them ends.
@@ -159,26 +303,1253 @@ async fn websocket(stream: WebSocket, state: Arc<AppState>) {
 
     if has_incremented_user_count.load(Ordering::SeqCst) {
         // Decrement & broadcast/send updated user_count
-        let tx = state.tx.clone();
+        let tx = state.channels[&page_name].clone();
         let mut write_lock = state.page_states.write().await;
-        let page_state = write_lock.get_mut("battlebit").unwrap();
+        let page_state = write_lock.get_mut(&page_name).unwrap();
 
         page_state.user_count -= 1;
-        tx.send(page_state.user_count as i64 * -1).unwrap();
+        gauge!(WEBSOCKET_CONNECTIONS, "page" => page_name.clone()).decrement(1);
+        let _ = tx.send(PageUpdate::UserCount(page_state.user_count));
     }
+
+    info!(duration = ?connected_at.elapsed(), "Websocket disconnected");
 }
 
-pub async fn battlebit(State(state): State<Arc<AppState>>) -> impl IntoResponse {
-    let mut write_lock = state.page_states.write().await;
-    let page_state = write_lock.get_mut("battlebit").unwrap();
+/// Applies a click to a page: pushes its datetime forward by an amount sampled from the page's
+/// increment distribution (or pulls it back, in hope mode), broadcasts it, and
+/// records the milestones it reached. Returns how far the datetime moved.
+fn apply_click(
+    state: &Arc<AppState>,
+    page_name: &str,
+    page_state: &mut PageState,
+    rng: &mut impl Rng,
+) -> TimeDelta {
+    let now = Utc::now();
+    if page_state.resolve_if_reached(now) {
+        record_milestone(state, page_name, Milestone::Resolved, page_state.datetime);
+    }
+    let click_count_before = page_state.click_count;
+    let latest_year_before = page_state.latest_year();
+
+    page_state.click_count += 1;
+    counter!(CLICKS_TOTAL, "page" => page_name.to_string()).increment(1);
+
+    let mut delta = sample_increment(&page_state.events, &page_state.increment, now, rng);
+    if let Some(hope) = &page_state.hope {
+        delta = hope.apply(delta, page_state.datetime, now, rng);
+    }
+    page_state.datetime = page_state.datetime.checked_add_signed(delta).unwrap();
+    let _ = state.channels[page_name].send(PageUpdate::Click {
+        datetime: page_state.datetime.timestamp_millis(),
+        delta: delta.num_milliseconds(),
+    });
+
+    for milestone in Milestone::after_clicks(
+        click_count_before,
+        page_state.click_count,
+        latest_year_before,
+        page_state.datetime,
+    ) {
+        record_milestone(state, page_name, milestone, page_state.datetime);
+    }
+    page_state.latest_year = Some(latest_year_before.max(page_state.datetime.year()));
+    delta
+}
+
+/// Announcements of the page's timed events that are on at `now`, for clients that just connected.
+fn active_event_messages(page_state: &PageState, now: DateTime<Utc>) -> Vec<ServerMessage> {
+    page_state
+        .events
+        .iter()
+        .filter(|event| event.is_active(now))
+        .map(TimedEvent::started_message)
+        .collect()
+}
+
+const FORWARDED_FOR_HEADER: &str = "x-forwarded-for";
+
+/// IP of the client that sent the request, for rate limiting.
+fn client_ip(state: &AppState, headers: &HeaderMap, addr: SocketAddr) -> IpAddr {
+    if state.config.rate_limit.trust_forwarded_for
+        && let Some(ip) = headers
+            .get(FORWARDED_FOR_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.rsplit(',').next())
+            .and_then(|ip| ip.trim().parse().ok())
+    {
+        return ip;
+    }
+    addr.ip()
+}
+
+/// Locale to format text in, picked from the `Accept-Language` header.
+fn request_locale(headers: &HeaderMap) -> Locale {
+    headers
+        .get(ACCEPT_LANGUAGE)
+        .and_then(|value| value.to_str().ok())
+        .map(Locale::from_accept_language)
+        .unwrap_or_default()
+}
+
+pub async fn battlebit(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let read_lock = state.page_states.read().await;
+    let page_state = &read_lock["battlebit"];
     let datetime = page_state.datetime;
 
+    let duration = SignedDuration::until(Utc::now().naive_utc(), datetime.naive_utc())
+        .with_precision(Precision::Seconds);
+
     let template = CountdownTemplate {
-        title: "BattleBit Remastered".to_string(),
-        datetime: datetime.timestamp(),
-        datetime_duration: datetime_difference(Utc::now().naive_utc(), datetime.naive_utc()),
+        title: page_state.title("battlebit").to_string(),
+        datetime: datetime.timestamp_millis(),
+        datetime_duration: format_signed_duration(
+            duration,
+            DurationStyle::Compact,
+            request_locale(&headers),
+        ),
+        overdue: duration.overdue,
+        og_image: og_image_url(&state, "battlebit", datetime),
+    };
+    drop(read_lock);
+
+    let html = template.render().unwrap();
+    (StatusCode::OK, Html(html)).into_response()
+}
+
+#[derive(Deserialize)]
+pub struct StatsParams {
+    #[serde(default = "default_resolution")]
+    resolution: Resolution,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+}
+
+fn default_resolution() -> Resolution {
+    Resolution::Hour
+}
+
+/// Rolled up click & user counts of a page, e.g. `/api/battlebit/stats?resolution=minute`.
+/// `from` and `to` are RFC 3339 datetimes, defaulting to a range that depends on the resolution.
+pub async fn stats(
+    Path(page_name): Path<String>,
+    Query(params): Query<StatsParams>,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    if !state.page_states.read().await.contains_key(&page_name) {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+
+    let to = params.to.unwrap_or_else(Utc::now);
+    let from = params
+        .from
+        .unwrap_or_else(|| to - params.resolution.default_range());
+    if from > to {
+        return (StatusCode::BAD_REQUEST, "`from` is after `to`").into_response();
+    }
+
+    match query_time_series_stats(
+        &state.db_pool,
+        &state.config.time_series,
+        &page_name,
+        params.resolution,
+        from,
+        to,
+    )
+    .await
+    {
+        Ok(stats) => Json(stats).into_response(),
+        Err(err) => {
+            error!(%err, "Failed to query stats");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Liveness check. Responds as long as the process is able to serve requests.
+pub async fn healthz() -> impl IntoResponse {
+    Json(json!({ "ok": true }))
+}
+
+#[derive(Serialize)]
+struct Readiness {
+    ok: bool,
+    components: BTreeMap<&'static str, ComponentStatus>,
+}
+
+/// Readiness check. Responds with `503 Service Unavailable` if any component is unhealthy, along
+/// with the status of each component.
+pub async fn readyz(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let now = Utc::now();
+    let components = BTreeMap::from([
+        ("save_file", check_writable(SAVE_FILE_PATH)),
+        ("database", check_database(&state.db_pool).await),
+        ("save_task", state.tasks_health.save.status(now)),
+        (
+            "insert_time_series_data_task",
+            state.tasks_health.insert_time_series_data.status(now),
+        ),
+        (
+            "daily_summary_task",
+            state.tasks_health.daily_summary.status(now),
+        ),
+        (
+            "timed_events_task",
+            state.tasks_health.timed_events.status(now),
+        ),
+    ]);
+    let ok = components.values().all(|status| status.ok);
+
+    let status_code = if ok {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status_code, Json(Readiness { ok, components }))
+}
+
+#[derive(Deserialize)]
+pub struct RemainingParams {
+    #[serde(default)]
+    style: DurationStyle,
+    #[serde(default)]
+    precision: Precision,
+    /// Overrides the locale picked from the `Accept-Language` header.
+    lang: Option<Locale>,
+}
+
+#[derive(Serialize)]
+struct Remaining {
+    remaining: String,
+    overdue: bool,
+    style: DurationStyle,
+    locale: Locale,
+}
+
+/// Time remaining until a page's datetime, formatted as text, e.g.
+/// `/api/battlebit/remaining?style=long&lang=de&precision=milliseconds`.
+pub async fn remaining(
+    Path(page_name): Path<String>,
+    Query(params): Query<RemainingParams>,
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let Some(datetime) = state
+        .page_states
+        .read()
+        .await
+        .get(&page_name)
+        .map(|page_state| page_state.datetime)
+    else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let locale = params.lang.unwrap_or_else(|| request_locale(&headers));
+    let duration = SignedDuration::until(Utc::now().naive_utc(), datetime.naive_utc())
+        .with_precision(params.precision);
+    Json(Remaining {
+        remaining: format_signed_duration(duration, params.style, locale),
+        overdue: duration.overdue,
+        style: params.style,
+        locale,
+    })
+    .into_response()
+}
+
+#[derive(Serialize)]
+struct PageSummary {
+    name: String,
+    title: String,
+    datetime: DateTime<Utc>,
+    /// Unix timestamp in milliseconds.
+    datetime_unix_ms: i64,
+    click_count: i64,
+    user_count: i32,
+    /// Time remaining until the datetime, or since it if overdue, in the compact style.
+    remaining: String,
+    overdue: bool,
+    resolved: bool,
+}
+
+/// Entity tag of a page's state. Only the click count and resolution change the datetime, so the
+/// user count and remaining time aren't part of it.
+fn page_etag(page_state: &PageState) -> String {
+    if page_state.resolved {
+        format!("\"{}-resolved\"", page_state.click_count)
+    } else {
+        format!("\"{}\"", page_state.click_count)
+    }
+}
+
+/// Whether an `If-None-Match` header matches the entity tag, using weak comparison.
+fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    if_none_match.split(',').map(str::trim).any(|candidate| {
+        candidate == "*" || candidate.strip_prefix("W/").unwrap_or(candidate) == etag
+    })
+}
+
+/// Current state of a page as JSON, e.g. `/api/battlebit`. Responds with `304 Not Modified` if
+/// `If-None-Match` matches, so that it can be polled cheaply.
+pub async fn page(
+    Path(page_name): Path<String>,
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let read_lock = state.page_states.read().await;
+    let Some(page_state) = read_lock.get(&page_name) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let etag = page_etag(page_state);
+    let cache_headers = [
+        (ETAG, etag.clone()),
+        (CACHE_CONTROL, "no-cache".to_string()),
+        // `remaining` is formatted in the request's language.
+        (VARY, ACCEPT_LANGUAGE.to_string()),
+    ];
+    if headers
+        .get(IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| etag_matches(value, &etag))
+    {
+        return (StatusCode::NOT_MODIFIED, cache_headers).into_response();
+    }
+
+    let duration = SignedDuration::until(Utc::now().naive_utc(), page_state.datetime.naive_utc())
+        .with_precision(Precision::Seconds);
+    let summary = PageSummary {
+        title: page_state.title(&page_name).to_string(),
+        datetime: page_state.datetime,
+        datetime_unix_ms: page_state.datetime.timestamp_millis(),
+        click_count: page_state.click_count,
+        user_count: page_state.user_count,
+        remaining: format_signed_duration(
+            duration,
+            DurationStyle::Compact,
+            request_locale(&headers),
+        ),
+        overdue: duration.overdue,
+        resolved: page_state.resolved,
+        name: page_name,
+    };
+    (cache_headers, Json(summary)).into_response()
+}
+
+/// Default number of daily summaries returned.
+const DEFAULT_DAILY_SUMMARIES: i64 = 30;
+/// Maximum number of daily summaries returned, about a year's worth.
+const MAX_DAILY_SUMMARIES: i64 = 366;
+
+#[derive(Deserialize)]
+pub struct DailySummariesParams {
+    limit: Option<i64>,
+}
+
+/// A page's latest daily summaries, newest first, e.g. `/api/battlebit/daily?limit=7`.
+pub async fn daily_summaries(
+    Path(page_name): Path<String>,
+    Query(params): Query<DailySummariesParams>,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    if !state.page_states.read().await.contains_key(&page_name) {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_DAILY_SUMMARIES)
+        .clamp(1, MAX_DAILY_SUMMARIES);
+    match query_daily_summaries(&state.db_pool, &page_name, limit).await {
+        Ok(summaries) => Json(summaries).into_response(),
+        Err(err) => {
+            error!(%err, "Failed to query daily summaries");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct TimeParams {
+    /// Echoed back, so that the client can measure the round trip without keeping track of it.
+    client_time: Option<i64>,
+}
+
+#[derive(Serialize)]
+struct ServerTime {
+    client_time: Option<i64>,
+    server_time: i64,
+}
+
+/// Current time of the server as a Unix timestamp in milliseconds, for clients whose clocks might
+/// be off, e.g. `/api/time?client_time=1760000000000`. Same as the websocket's time sync.
+pub async fn time(Query(params): Query<TimeParams>) -> impl IntoResponse {
+    (
+        [(CACHE_CONTROL, "no-store")],
+        Json(ServerTime {
+            client_time: params.client_time,
+            server_time: Utc::now().timestamp_millis(),
+        }),
+    )
+}
+
+/// Content type of iCalendar feeds.
+const CALENDAR_CONTENT_TYPE: &str = "text/calendar; charset=utf-8";
+
+fn calendar_event<'a>(page_name: &'a str, page_state: &'a PageState) -> CalendarEvent<'a> {
+    CalendarEvent {
+        uid: format!("{}@update-countdown", page_name),
+        summary: page_state.title(page_name),
+        start: page_state.datetime,
+        // Every click moves the datetime, so the click count only goes up when the event changes.
+        sequence: page_state.click_count,
+    }
+}
+
+/// iCalendar feed with a single event at a page's datetime, e.g. `/battlebit/calendar.ics`.
+pub async fn calendar(
+    Path(page_name): Path<String>,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    let read_lock = state.page_states.read().await;
+    let Some(page_state) = read_lock.get(&page_name) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let ics = ical::calendar(
+        page_state.title(&page_name),
+        &[calendar_event(&page_name, page_state)],
+        Utc::now(),
+    );
+    ([(CONTENT_TYPE, CALENDAR_CONTENT_TYPE)], ics).into_response()
+}
+
+/// iCalendar feed with an event for every page.
+pub async fn calendar_all(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let read_lock = state.page_states.read().await;
+    // Sorted so that the feed doesn't change between requests if nothing else did.
+    let mut events = read_lock
+        .iter()
+        .map(|(page_name, page_state)| calendar_event(page_name, page_state))
+        .collect::<Vec<_>>();
+    events.sort_by(|a, b| a.uid.cmp(&b.uid));
+
+    let ics = ical::calendar("Update countdown", &events, Utc::now());
+    ([(CONTENT_TYPE, CALENDAR_CONTENT_TYPE)], ics)
+}
+
+/// Content type of Atom feeds.
+const ATOM_CONTENT_TYPE: &str = "application/atom+xml; charset=utf-8";
+/// Maximum number of entries in a feed.
+const MAX_FEED_ENTRIES: i64 = 50;
+
+/// Atom feed of a page's milestones and daily summaries, e.g. `/battlebit/feed.atom`.
+pub async fn feed(
+    Path(page_name): Path<String>,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    let Some(title) = state
+        .page_states
+        .read()
+        .await
+        .get(&page_name)
+        .map(|page_state| page_state.title(&page_name).to_string())
+    else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let milestones = match query_milestones(&state.db_pool, &page_name, MAX_FEED_ENTRIES).await {
+        Ok(milestones) => milestones,
+        Err(err) => {
+            error!(%err, "Failed to query milestones");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let summaries = match query_daily_summaries(&state.db_pool, &page_name, MAX_FEED_ENTRIES).await
+    {
+        Ok(summaries) => summaries,
+        Err(err) => {
+            error!(%err, "Failed to query daily summaries");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let milestone_entries = milestones.into_iter().map(|entry| FeedEntry {
+        id: format!("urn:update-countdown:{}:milestone:{}", page_name, entry.id),
+        title: entry.milestone.title(&title),
+        updated: entry.timestamp,
+        summary: format!(
+            "The update was expected on {}.",
+            entry.datetime.format("%Y-%m-%d %H:%M:%S UTC")
+        ),
+    });
+    let summary_entries = summaries.into_iter().map(|summary| FeedEntry {
+        id: format!("urn:update-countdown:{}:daily:{}", page_name, summary.date),
+        title: format!("{} on {}", title, summary.date),
+        // Summarized once the day is over.
+        updated: (summary.date + TimeDelta::days(1))
+            .and_hms_opt(0, 0, 0)
+            .unwrap()
+            .and_utc(),
+        summary: format!(
+            "{}.",
+            daily_summary_message(
+                &title,
+                summary.date,
+                summary.clicks_added,
+                summary.seconds_added,
+                summary.peak_user_count,
+            )
+        ),
+    });
+    let mut entries = milestone_entries.chain(summary_entries).collect::<Vec<_>>();
+    entries.sort_by_key(|entry| std::cmp::Reverse(entry.updated));
+    entries.truncate(MAX_FEED_ENTRIES as usize);
+
+    let xml = atom_feed(
+        &format!("urn:update-countdown:{}", page_name),
+        &title,
+        &format!("/{}", page_name),
+        &entries,
+    );
+    ([(CONTENT_TYPE, ATOM_CONTENT_TYPE)], xml).into_response()
+}
+
+/// Badges can be cached for a minute, which is about as stale as a badge in a README can get
+/// anyway, since image proxies cache them too.
+const BADGE_CACHE_CONTROL: &str = "public, max-age=60";
+
+#[derive(Deserialize)]
+pub struct BadgeParams {
+    #[serde(default)]
+    style: BadgeStyle,
+    /// Defaults to e.g. `BattleBit Remastered updates in`.
+    label: Option<String>,
+    /// Name (e.g. `green`) or hex code without the `#` (e.g. `ff69b4`) of the message's
+    /// background.
+    color: Option<String>,
+}
+
+/// Text of a page's badge, e.g. `812d 3h 0m 12s` or `overdue by 3d 2h 0m 0s`. Milliseconds are
+/// left out, since the badge is cached for a while anyway.
+fn badge_message(now: NaiveDateTime, datetime: NaiveDateTime) -> String {
+    let parts = DurationParts {
+        milliseconds: 0,
+        ..duration_parts(now, datetime)
+    };
+    let formatted = format_duration(parts, DurationStyle::Compact, Locale::En);
+    if datetime < now {
+        Locale::En.overdue(&formatted)
+    } else {
+        formatted
+    }
+}
+
+/// SVG badge showing the time remaining until a page's datetime, e.g.
+/// `/battlebit/badge.svg?style=flat-square&label=BattleBit&color=orange`.
+pub async fn badge(
+    Path(page_name): Path<String>,
+    Query(params): Query<BadgeParams>,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    let Some((title, datetime)) =
+        state
+            .page_states
+            .read()
+            .await
+            .get(&page_name)
+            .map(|page_state| {
+                (
+                    page_state.title(&page_name).to_string(),
+                    page_state.datetime,
+                )
+            })
+    else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let now = Utc::now().naive_utc();
+    let datetime = datetime.naive_utc();
+    let message = badge_message(now, datetime);
+    let (default_label, default_color) = if datetime < now {
+        (format!("{} update", title), "red")
+    } else {
+        (format!("{} updates in", title), "blue")
+    };
+
+    let Some(color) = parse_color(params.color.as_deref().unwrap_or(default_color)) else {
+        return (StatusCode::BAD_REQUEST, "Invalid `color`").into_response();
+    };
+    let label = params.label.unwrap_or(default_label);
+
+    (
+        [
+            (CONTENT_TYPE, "image/svg+xml; charset=utf-8"),
+            (CACHE_CONTROL, BADGE_CACHE_CONTROL),
+        ],
+        render_badge(&label, &message, &color, params.style),
+    )
+        .into_response()
+}
+
+/// URL of a page's Open Graph image, which has to be absolute, so there's none without
+/// `public_url`. The datetime is added to the query so that link previews aren't stuck on an old
+/// image after it changes.
+fn og_image_url(state: &AppState, page_name: &str, datetime: DateTime<Utc>) -> Option<String> {
+    let public_url = state.config.public_url.as_ref()?;
+    Some(format!(
+        "{}/{}/og.png?v={}",
+        public_url,
+        page_name,
+        datetime.timestamp_millis()
+    ))
+}
+
+/// Open Graph image of a page, showing its title and the time remaining, e.g.
+/// `/battlebit/og.png`.
+pub async fn og_image(
+    Path(page_name): Path<String>,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    let Some((title, datetime)) =
+        state
+            .page_states
+            .read()
+            .await
+            .get(&page_name)
+            .map(|page_state| {
+                (
+                    page_state.title(&page_name).to_string(),
+                    page_state.datetime,
+                )
+            })
+    else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let now = Utc::now();
+    let png = state
+        .og_images
+        .get_or_render(&page_name, now, move || {
+            let duration = SignedDuration::until(now.naive_utc(), datetime.naive_utc())
+                .with_precision(Precision::Seconds);
+            let caption = if duration.overdue {
+                "update is"
+            } else {
+                "updates in"
+            };
+            let remaining = format_signed_duration(duration, DurationStyle::Compact, Locale::En);
+            render_og_image(&title, caption, &remaining)
+        })
+        .await;
+
+    (
+        [
+            (CONTENT_TYPE, "image/png".to_string()),
+            (
+                CACHE_CONTROL,
+                format!("public, max-age={}", og_image::CACHE_INTERVAL.as_secs()),
+            ),
+        ],
+        png,
+    )
+        .into_response()
+}
+
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    #[default]
+    Dark,
+    Light,
+}
+
+impl fmt::Display for Theme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Theme::Dark => "dark",
+            Theme::Light => "light",
+        })
+    }
+}
+
+const DEFAULT_EMBED_FONT_SIZE: u16 = 48;
+const EMBED_FONT_SIZE_RANGE: RangeInclusive<u16> = 8..=256;
+
+#[derive(Deserialize)]
+pub struct EmbedParams {
+    #[serde(default)]
+    theme: Theme,
+    /// Font size of the countdown in pixels, clamped to `EMBED_FONT_SIZE_RANGE`.
+    font_size: Option<u16>,
+    /// Whether to show the refresh button.
+    #[serde(default = "default_true")]
+    button: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Minimal countdown of a page to embed in other sites with an iframe, e.g.
+/// `/battlebit/embed?theme=light&font_size=32&button=false`.
+pub async fn embed(
+    Path(page_name): Path<String>,
+    Query(params): Query<EmbedParams>,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    let Some((title, datetime)) =
+        state
+            .page_states
+            .read()
+            .await
+            .get(&page_name)
+            .map(|page_state| {
+                (
+                    page_state.title(&page_name).to_string(),
+                    page_state.datetime,
+                )
+            })
+    else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let font_size = params
+        .font_size
+        .unwrap_or(DEFAULT_EMBED_FONT_SIZE)
+        .clamp(*EMBED_FONT_SIZE_RANGE.start(), *EMBED_FONT_SIZE_RANGE.end());
+
+    let template = EmbedTemplate {
+        title,
+        page_name,
+        datetime: datetime.timestamp_millis(),
+        theme: params.theme,
+        font_size,
+        show_button: params.button,
     };
 
     let html = template.render().unwrap();
     (StatusCode::OK, Html(html)).into_response()
 }
+
+/// Counts an event stream as a user of a page until it's dropped, like a websocket.
+struct EventStreamViewer {
+    state: Arc<AppState>,
+    page_name: String,
+}
+
+impl EventStreamViewer {
+    async fn connect(state: Arc<AppState>, page_name: String) -> Self {
+        let mut write_lock = state.page_states.write().await;
+        let page_state = write_lock.get_mut(&page_name).unwrap();
+        page_state.user_count += 1;
+        gauge!(EVENT_STREAM_CONNECTIONS, "page" => page_name.clone()).increment(1);
+        let _ = state.channels[&page_name].send(PageUpdate::UserCount(page_state.user_count));
+        drop(write_lock);
+
+        Self { state, page_name }
+    }
+}
+
+impl Drop for EventStreamViewer {
+    fn drop(&mut self) {
+        let state = self.state.clone();
+        let page_name = std::mem::take(&mut self.page_name);
+        tokio::spawn(async move {
+            let mut write_lock = state.page_states.write().await;
+            let page_state = write_lock.get_mut(&page_name).unwrap();
+            page_state.user_count -= 1;
+            gauge!(EVENT_STREAM_CONNECTIONS, "page" => page_name.clone()).decrement(1);
+            let _ = state.channels[&page_name].send(PageUpdate::UserCount(page_state.user_count));
+        });
+    }
+}
+
+/// How long `EventSource` clients wait before reconnecting, e.g. after the server restarts.
+const EVENT_STREAM_RETRY: Duration = Duration::from_secs(5);
+
+/// Turns an update from a page's channel into a server-sent event.
+fn channel_event(update: PageUpdate) -> Event {
+    match update {
+        PageUpdate::Datetime(datetime) => Event::default()
+            .event("datetime")
+            .data(datetime.to_string()),
+        PageUpdate::Click { datetime, delta } => Event::default()
+            .event("click")
+            .data(json!({ "datetime": datetime, "delta": delta }).to_string()),
+        PageUpdate::UserCount(user_count) => Event::default()
+            .event("user_count")
+            .data(user_count.to_string()),
+        PageUpdate::Announcement(message) => announcement_event(&message),
+    }
+}
+
+/// Turns a control message into an `announcement` server-sent event, with the same JSON as the
+/// websocket's text frames.
+fn announcement_event(message: &ServerMessage) -> Event {
+    Event::default()
+        .event("announcement")
+        .data(serde_json::to_string(message).unwrap())
+}
+
+/// Server-sent events with the same updates as a page's websocket, for clients that can't use
+/// websockets: `datetime` events with the datetime as a Unix timestamp in milliseconds, `click`
+/// events with the new datetime and how far the click moved it (e.g.
+/// `{"datetime":1760000000000,"delta":-1500}`), `user_count` events, and `announcement` events with
+/// the same JSON as the websocket's control messages. Clicks themselves are sent with
+/// `POST /api/{page}/click` instead.
+pub async fn events(
+    Path(page_name): Path<String>,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    let Some(tx) = state.channels.get(&page_name) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    // Subscribe before connecting, so that the incremented user count is received.
+    let rx = tx.subscribe();
+    let (datetime, active_events) = {
+        let read_lock = state.page_states.read().await;
+        let page_state = &read_lock[&page_name];
+        (
+            page_state.datetime,
+            active_event_messages(page_state, Utc::now()),
+        )
+    };
+    let viewer = EventStreamViewer::connect(state.clone(), page_name).await;
+
+    let mut first = vec![
+        channel_event(PageUpdate::Datetime(datetime.timestamp_millis())).retry(EVENT_STREAM_RETRY),
+    ];
+    first.extend(active_events.iter().map(announcement_event));
+    let updates = stream::unfold(rx, |mut rx| async move {
+        loop {
+            match rx.recv().await {
+                Ok(msg) => return Some((channel_event(msg), rx)),
+                Err(RecvError::Lagged(num_skipped)) => {
+                    counter!(BROADCAST_LAG_EVENTS_TOTAL).increment(1);
+                    counter!(BROADCAST_LAGGED_MESSAGES_TOTAL).increment(num_skipped);
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+    let stream = stream::iter(first)
+        .chain(updates)
+        // The viewer is moved into the stream, so that it disconnects once the stream is dropped.
+        .map(move |event| {
+            let _ = &viewer;
+            Ok::<_, Infallible>(event)
+        })
+        // Otherwise graceful shutdown would wait for the stream forever. Clients reconnect after
+        // `EVENT_STREAM_RETRY`.
+        .take_until(state.shutdown.clone().cancelled_owned());
+
+    Sse::new(stream)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+/// Header that clients can set on clicks to make retries safe.
+const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+/// Set on responses to clicks that were already applied by an earlier request with the same
+/// idempotency key.
+const IDEMPOTENT_REPLAYED_HEADER: &str = "idempotent-replayed";
+
+#[derive(Serialize, Clone)]
+pub struct ClickResult {
+    /// The page's new datetime.
+    datetime: DateTime<Utc>,
+    /// How far the click pushed the datetime, which is negative if it pulled it back instead.
+    seconds_added: f64,
+}
+
+/// Clicks a page without a websocket, e.g. `POST /api/battlebit/click`. If the `Idempotency-Key`
+/// header is set, retries with the same key return the original result instead of clicking again.
+pub async fn click(
+    Path(page_name): Path<String>,
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    // Stop accepting clicks once shutting down, so that the saved state is final.
+    if state.shutdown.is_cancelled() {
+        return StatusCode::SERVICE_UNAVAILABLE.into_response();
+    }
+
+    let idempotency_key = match headers.get(IDEMPOTENCY_KEY_HEADER) {
+        None => None,
+        Some(value) => match value.to_str() {
+            Ok(key) if !key.is_empty() && key.len() <= MAX_IDEMPOTENCY_KEY_LEN => Some(key),
+            _ => {
+                return (StatusCode::BAD_REQUEST, "Invalid `Idempotency-Key`").into_response();
+            }
+        },
+    };
+
+    if !state.channels.contains_key(&page_name) {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+
+    let now = Instant::now();
+    let replay = |result| ([(IDEMPOTENT_REPLAYED_HEADER, "true")], Json(result)).into_response();
+    if let Some(key) = idempotency_key
+        && let Some(result) = state.click_results.get(&page_name, key, now)
+    {
+        return replay(result);
+    }
+
+    if let Err(retry_after) = state
+        .click_limiter
+        .check(client_ip(&state, &headers, addr), now)
+    {
+        return (
+            StatusCode::TOO_MANY_REQUESTS,
+            [(RETRY_AFTER, retry_after.as_secs().max(1).to_string())],
+        )
+            .into_response();
+    }
+
+    let mut write_lock = state.page_states.write().await;
+    let page_state = write_lock.get_mut(&page_name).unwrap();
+    // Checked again while holding the lock, in case a concurrent retry was applied in the meantime.
+    if let Some(key) = idempotency_key
+        && let Some(result) = state.click_results.get(&page_name, key, now)
+    {
+        return replay(result);
+    }
+
+    let added = apply_click(&state, &page_name, page_state, &mut rand::rng());
+    let result = ClickResult {
+        datetime: page_state.datetime,
+        seconds_added: added.num_milliseconds() as f64 / 1000.0,
+    };
+    if let Some(key) = idempotency_key {
+        state
+            .click_results
+            .insert(&page_name, key, result.clone(), now);
+    }
+
+    Json(result).into_response()
+}
+
+/// Compares in constant time, so that a token can't be guessed from how long it takes to reject.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+/// Checks that the request has the admin token as `Authorization: Bearer <token>`, returning the
+/// response to send if it doesn't. The admin API responds with `404 Not Found` if it's disabled.
+fn admin_rejection(state: &AppState, headers: &HeaderMap) -> Option<Response> {
+    let Some(admin_token) = &state.config.admin_token else {
+        return Some(StatusCode::NOT_FOUND.into_response());
+    };
+    let token = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    match token {
+        Some(token) if constant_time_eq(token.as_bytes(), admin_token.as_bytes()) => None,
+        _ => Some((StatusCode::UNAUTHORIZED, [(WWW_AUTHENTICATE, "Bearer")]).into_response()),
+    }
+}
+
+/// Events scheduled on a page, including past ones, e.g. `GET /api/battlebit/events`. Part of the
+/// admin API.
+pub async fn list_events(
+    Path(page_name): Path<String>,
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    if let Some(response) = admin_rejection(&state, &headers) {
+        return response;
+    }
+    match state.page_states.read().await.get(&page_name) {
+        Some(page_state) => Json(page_state.events.clone()).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+/// Schedules an event on a page, e.g. `POST /api/battlebit/events` with a `TimedEvent` as JSON.
+/// Event names are unique per page, since events are cancelled by name. An event that should have
+/// started already starts now instead, so that it's still announced. Part of the admin API.
+pub async fn create_event(
+    Path(page_name): Path<String>,
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(mut event): Json<TimedEvent>,
+) -> impl IntoResponse {
+    if let Some(response) = admin_rejection(&state, &headers) {
+        return response;
+    }
+    if let Err(err) = event.validate() {
+        return (StatusCode::BAD_REQUEST, err).into_response();
+    }
+    let now = Utc::now();
+    if event.end <= now {
+        return (StatusCode::BAD_REQUEST, "`end` must be in the future").into_response();
+    }
+    event.start = event.start.max(now);
+
+    let mut write_lock = state.page_states.write().await;
+    let Some(page_state) = write_lock.get_mut(&page_name) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    if page_state
+        .events
+        .iter()
+        .any(|other| other.name == event.name)
+    {
+        return (
+            StatusCode::CONFLICT,
+            "An event with this name already exists",
+        )
+            .into_response();
+    }
+    info!(page_name, ?event, "Scheduled event");
+    page_state.events.push(event.clone());
+    drop(write_lock);
+    // The timed events task announces the event when it starts.
+    state.timed_events_changed.notify_one();
+
+    (StatusCode::CREATED, Json(event)).into_response()
+}
+
+/// Cancels an event, e.g. `DELETE /api/battlebit/events/Double%20time%20weekend`, telling clients
+/// that it ended if it's on. Part of the admin API.
+pub async fn cancel_event(
+    Path((page_name, event_name)): Path<(String, String)>,
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    if let Some(response) = admin_rejection(&state, &headers) {
+        return response;
+    }
+
+    let mut write_lock = state.page_states.write().await;
+    let Some(page_state) = write_lock.get_mut(&page_name) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let Some(index) = page_state
+        .events
+        .iter()
+        .position(|event| event.name == event_name)
+    else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let event = page_state.events.remove(index);
+    info!(page_name, ?event, "Cancelled event");
+    if event.is_active(Utc::now()) {
+        // Sending only fails if nobody is subscribed, in which case there's nobody to tell.
+        let _ = state.channels[&page_name].send(PageUpdate::Announcement(event.ended_message()));
+    }
+    drop(write_lock);
+    state.timed_events_changed.notify_one();
+
+    StatusCode::NO_CONTENT.into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, SocketAddr};
+    use std::sync::Arc;
+
+    use axum::extract::{ConnectInfo, Path, State};
+    use axum::http::header::{AUTHORIZATION, ETAG, IF_NONE_MATCH, VARY};
+    use axum::http::{HeaderMap, HeaderValue, StatusCode};
+    use axum::response::{IntoResponse, Json};
+    use chrono::{TimeDelta, TimeZone, Utc};
+    use sqlx::PgPool;
+
+    use crate::config::Config;
+    use crate::protocol::{PageUpdate, ServerMessage};
+    use crate::routes::{
+        IDEMPOTENCY_KEY_HEADER, IDEMPOTENT_REPLAYED_HEADER, badge_message, cancel_event, click,
+        create_event, etag_matches, page, page_etag,
+    };
+    use crate::timed_events::TimedEvent;
+    use crate::webhooks::WebhookQueue;
+    use crate::{AppState, PageState};
+
+    /// State with a single `battlebit` page and `secret` as the admin token. The database isn't
+    /// connected to, so clicks must not reach any milestones.
+    fn test_state(test_name: &str) -> Arc<AppState> {
+        let path = std::env::temp_dir().join(format!(
+            "update-countdown-{}-{}.json",
+            test_name,
+            std::process::id()
+        ));
+        let page_state = PageState {
+            title: None,
+            datetime: Utc::now() + TimeDelta::days(365),
+            user_count: 0,
+            click_count: 0,
+            resolved: false,
+            increment: Default::default(),
+            hope: None,
+            latest_year: None,
+            events: Vec::new(),
+        };
+        let save = serde_json::json!({ "battlebit": page_state });
+        std::fs::write(&path, save.to_string()).unwrap();
+        let db_pool = PgPool::connect_lazy("postgres://localhost/unused").unwrap();
+        let (webhooks, _rx) = WebhookQueue::new();
+        let config = Config {
+            admin_token: Some("secret".to_string()),
+            ..Default::default()
+        };
+        let state = AppState::load(&path, config, db_pool, webhooks);
+        std::fs::remove_file(path).unwrap();
+        Arc::new(state)
+    }
+
+    #[tokio::test]
+    async fn etag() {
+        let state = test_state("etag");
+        let mut page_states = state.page_states.write().await;
+        let page_state = page_states.get_mut("battlebit").unwrap();
+        assert_eq!(page_etag(page_state), r#""0""#);
+        page_state.click_count = 12;
+        assert_eq!(page_etag(page_state), r#""12""#);
+        page_state.resolved = true;
+        assert_eq!(page_etag(page_state), r#""12-resolved""#);
+        drop(page_states);
+
+        assert!(etag_matches(r#""12""#, r#""12""#));
+        assert!(etag_matches(r#"W/"12""#, r#""12""#));
+        assert!(etag_matches(r#""11", W/"12""#, r#""12""#));
+        assert!(etag_matches("*", r#""12""#));
+        assert!(!etag_matches(r#""11""#, r#""12""#));
+        assert!(!etag_matches("12", r#""12""#));
+
+        let get = |if_none_match: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(IF_NONE_MATCH, HeaderValue::from_static(if_none_match));
+            page(Path("battlebit".to_string()), State(state.clone()), headers)
+        };
+        let not_modified = get(r#"W/"12-resolved""#).await.into_response();
+        assert_eq!(not_modified.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(not_modified.headers()[ETAG], r#""12-resolved""#);
+        assert_eq!(not_modified.headers()[VARY], "accept-language");
+        let modified = get(r#""12""#).await.into_response();
+        assert_eq!(modified.status(), StatusCode::OK);
+        assert_eq!(modified.headers()[VARY], "accept-language");
+    }
+
+    #[tokio::test]
+    async fn idempotent_click() {
+        let state = test_state("idempotent_click");
+        let addr = ConnectInfo(SocketAddr::from((Ipv4Addr::LOCALHOST, 1234)));
+        let mut headers = HeaderMap::new();
+        headers.insert(IDEMPOTENCY_KEY_HEADER, HeaderValue::from_static("abc"));
+        let click = |headers: HeaderMap| {
+            click(
+                Path("battlebit".to_string()),
+                State(state.clone()),
+                addr,
+                headers,
+            )
+        };
+
+        let first = click(headers.clone()).await.into_response();
+        assert_eq!(first.status(), StatusCode::OK);
+        assert!(!first.headers().contains_key(IDEMPOTENT_REPLAYED_HEADER));
+        let retry = click(headers).await.into_response();
+        assert_eq!(retry.status(), StatusCode::OK);
+        assert_eq!(retry.headers()[IDEMPOTENT_REPLAYED_HEADER], "true");
+        assert_eq!(state.page_states.read().await["battlebit"].click_count, 1);
+
+        // Without a key, every request is a new click.
+        click(HeaderMap::new()).await;
+        click(HeaderMap::new()).await;
+        assert_eq!(state.page_states.read().await["battlebit"].click_count, 3);
+    }
+
+    #[test]
+    fn badge() {
+        let now = Utc
+            .with_ymd_and_hms(2026, 10, 18, 12, 0, 0)
+            .unwrap()
+            .naive_utc()
+            + TimeDelta::milliseconds(250);
+        let datetime =
+            now + TimeDelta::days(812) + TimeDelta::hours(3) + TimeDelta::milliseconds(12_345);
+        assert_eq!(badge_message(now, datetime), "812d 3h 0m 12s");
+        assert_eq!(
+            badge_message(
+                now,
+                now - TimeDelta::days(3) - TimeDelta::milliseconds(7_200_500)
+            ),
+            "overdue by 3d 2h 0m 0s"
+        );
+    }
+
+    #[tokio::test]
+    async fn admin_events() {
+        let state = test_state("admin_events");
+        let mut rx = state.channels["battlebit"].subscribe();
+        let authorized = |token: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(AUTHORIZATION, HeaderValue::from_static(token));
+            headers
+        };
+        let now = Utc::now();
+        let event = TimedEvent {
+            name: "Double time weekend".to_string(),
+            start: now - TimeDelta::hours(1),
+            end: now + TimeDelta::days(2),
+            multiplier: 2.0,
+            increment: None,
+        };
+        let create = |headers: HeaderMap| {
+            create_event(
+                Path("battlebit".to_string()),
+                State(state.clone()),
+                headers,
+                Json(event.clone()),
+            )
+        };
+        let cancel = || {
+            cancel_event(
+                Path(("battlebit".to_string(), "Double time weekend".to_string())),
+                State(state.clone()),
+                authorized("Bearer secret"),
+            )
+        };
+
+        let unauthorized = create(authorized("Bearer wrong")).await.into_response();
+        assert_eq!(unauthorized.status(), StatusCode::UNAUTHORIZED);
+        let unauthorized = create(HeaderMap::new()).await.into_response();
+        assert_eq!(unauthorized.status(), StatusCode::UNAUTHORIZED);
+
+        let created = create(authorized("Bearer secret")).await.into_response();
+        assert_eq!(created.status(), StatusCode::CREATED);
+        // Starts now instead of in the past, so that the timed events task announces it.
+        let events = state.page_states.read().await["battlebit"].events.clone();
+        assert_eq!(events.len(), 1);
+        assert!(events[0].start >= now);
+        let duplicate = create(authorized("Bearer secret")).await.into_response();
+        assert_eq!(duplicate.status(), StatusCode::CONFLICT);
+
+        assert_eq!(
+            cancel().await.into_response().status(),
+            StatusCode::NO_CONTENT
+        );
+        assert!(
+            state.page_states.read().await["battlebit"]
+                .events
+                .is_empty()
+        );
+        assert_eq!(
+            rx.try_recv().unwrap(),
+            PageUpdate::Announcement(ServerMessage::EventEnded {
+                name: "Double time weekend".to_string()
+            })
+        );
+        assert_eq!(
+            cancel().await.into_response().status(),
+            StatusCode::NOT_FOUND
+        );
+    }
+}
@@ -0,0 +1,304 @@
+use std::convert::Infallible;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, TimeDelta, Utc};
+use futures::FutureExt;
+use metrics::{counter, histogram};
+use tokio::time::{interval, sleep};
+use tracing::{Instrument, error, info, info_span, warn};
+
+use crate::db::{insert_daily_summaries, insert_time_series_page_data};
+use crate::health::TaskHealth;
+use crate::protocol::PageUpdate;
+use crate::telemetry::{DB_INSERT_DURATION_SECONDS, DB_INSERT_FAILURES_TOTAL, TASK_FAILURES_TOTAL};
+use crate::timed_events::{next_transition, transitions};
+use crate::webhooks::{WebhookEvent, WebhookPayload};
+use crate::{
+    AppState, SAVE_FILE_PATH, SAVE_INTERVAL, TIME_SERIES_INSERT_INTERVAL, TIMED_EVENTS_MAX_SLEEP,
+};
+
+/// Error returned by a supervised task.
+#[derive(Debug)]
+pub enum TaskError {
+    /// The task is restarted after a backoff.
+    Transient(String),
+    /// Retrying won't help, so the server is shut down.
+    Fatal(String),
+}
+
+impl From<std::io::Error> for TaskError {
+    fn from(err: std::io::Error) -> Self {
+        TaskError::Transient(err.to_string())
+    }
+}
+
+impl From<sqlx::Error> for TaskError {
+    fn from(err: sqlx::Error) -> Self {
+        match &err {
+            // SQLSTATE class 42 is "syntax error or access rule violation", e.g. a missing table
+            // or column, which means the schema doesn't match what the code expects.
+            sqlx::Error::Database(db_err)
+                if db_err.code().is_some_and(|code| code.starts_with("42")) =>
+            {
+                TaskError::Fatal(err.to_string())
+            }
+            _ => TaskError::Transient(err.to_string()),
+        }
+    }
+}
+
+/// Delay between restarts of a failing task, doubling after each consecutive failure.
+pub struct Backoff {
+    pub initial: Duration,
+    pub max: Duration,
+    /// If the task ran for at least this long before failing, the delay is reset to `initial`.
+    pub reset_after: Duration,
+}
+
+pub const DEFAULT_BACKOFF: Backoff = Backoff {
+    initial: Duration::from_secs(1),
+    max: Duration::from_secs(60),
+    reset_after: Duration::from_secs(60),
+};
+
+/// Runs the task created by `make_task`, restarting it with a backoff whenever it returns a
+/// transient error or panics. Only returns if the task fails with a fatal error.
+///
+/// NOTE: Panics are only caught if the binary is built with `panic = "unwind"`, which isn't the
+/// case for release builds, so tasks should return errors instead of panicking.
+pub async fn supervise<F, Fut>(
+    name: &'static str,
+    health: &TaskHealth,
+    backoff: Backoff,
+    mut make_task: F,
+) -> String
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<Infallible, TaskError>>,
+{
+    let mut delay = backoff.initial;
+    loop {
+        let started_at = Instant::now();
+        let result = {
+            let _running = health.start();
+            AssertUnwindSafe(make_task()).catch_unwind().await
+        };
+
+        let err = match result {
+            Ok(Err(TaskError::Fatal(err))) => {
+                counter!(TASK_FAILURES_TOTAL, "task" => name).increment(1);
+                health.record_failure(&err);
+                error!(task = name, err, "Task failed with a fatal error");
+                return err;
+            }
+            Ok(Err(TaskError::Transient(err))) => err,
+            Err(panic) => panic
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "panicked".to_string()),
+        };
+
+        if started_at.elapsed() >= backoff.reset_after {
+            delay = backoff.initial;
+        }
+        counter!(TASK_FAILURES_TOTAL, "task" => name).increment(1);
+        health.record_failure(&err);
+        warn!(task = name, err, restart_in = ?delay, "Task failed, restarting");
+
+        sleep(delay).await;
+        delay = (delay * 2).min(backoff.max);
+    }
+}
+
+/// Periodically saves the state to the save file.
+pub async fn save_task(state: Arc<AppState>) -> Result<Infallible, TaskError> {
+    let mut save_interval = interval(SAVE_INTERVAL);
+    // Do this because first tick completes immediately
+    save_interval.tick().await;
+    async move {
+        loop {
+            save_interval.tick().await;
+            state.save(SAVE_FILE_PATH).await?;
+            state.tasks_health.save.record_success();
+            info!("Saved state");
+        }
+    }
+    .instrument(info_span!("save_task"))
+    .await
+}
+
+/// Periodically inserts a snapshot of every page into the time series table.
+pub async fn insert_time_series_data_task(state: Arc<AppState>) -> Result<Infallible, TaskError> {
+    let mut interval = interval(TIME_SERIES_INSERT_INTERVAL);
+    // Do this because first tick completes immediately
+    interval.tick().await;
+    async move {
+        loop {
+            interval.tick().await;
+            let data = state.get_time_series_data_entries().await;
+            let start = Instant::now();
+            let result = insert_time_series_page_data(&state.db_pool, data).await;
+
+            histogram!(DB_INSERT_DURATION_SECONDS).record(start.elapsed());
+            if let Err(err) = &result {
+                counter!(DB_INSERT_FAILURES_TOTAL).increment(1);
+                error!(%err, "Failed to insert time series data");
+            }
+            result?;
+            state.tasks_health.insert_time_series_data.record_success();
+        }
+    }
+    .instrument(info_span!("insert_time_series_data_task"))
+    .await
+}
+
+/// How long after midnight (UTC) the previous day is summarized, so that its last snapshots have
+/// been inserted.
+const DAILY_SUMMARY_DELAY: TimeDelta = TimeDelta::minutes(1);
+
+/// Time until the next day should be summarized.
+fn until_next_summary(now: DateTime<Utc>) -> Duration {
+    let next = now.date_naive().and_hms_opt(0, 0, 0).unwrap().and_utc() + DAILY_SUMMARY_DELAY;
+    let next = if next > now {
+        next
+    } else {
+        next + TimeDelta::days(1)
+    };
+    (next - now).to_std().unwrap()
+}
+
+/// Summarizes the stats of every page over the previous day once per day, and sends the summaries
+/// to webhooks. The previous day is also summarized on startup if it wasn't already, in case the
+/// server was down at the time.
+pub async fn daily_summary_task(state: Arc<AppState>) -> Result<Infallible, TaskError> {
+    async move {
+        loop {
+            let yesterday = Utc::now().date_naive().pred_opt().unwrap();
+            let summaries = insert_daily_summaries(&state.db_pool, yesterday).await?;
+            state.tasks_health.daily_summary.record_success();
+
+            for summary in summaries {
+                info!(?summary, "Summarized day");
+                let Some(title) = state
+                    .page_states
+                    .read()
+                    .await
+                    .get(&summary.page_name)
+                    .map(|page_state| page_state.title(&summary.page_name).to_string())
+                else {
+                    continue;
+                };
+                let event = WebhookEvent::DailySummary {
+                    date: summary.date,
+                    clicks_added: summary.clicks_added,
+                    seconds_added: summary.seconds_added,
+                    peak_user_count: summary.peak_user_count,
+                };
+                state.webhooks.push(WebhookPayload {
+                    message: event.message(&title),
+                    title,
+                    page: summary.page_name,
+                    timestamp: Utc::now(),
+                    event,
+                });
+            }
+
+            sleep(until_next_summary(Utc::now())).await;
+        }
+    }
+    .instrument(info_span!("daily_summary_task"))
+    .await
+}
+
+/// Announces timed events to everyone viewing their page as they start and end.
+pub async fn timed_events_task(state: Arc<AppState>) -> Result<Infallible, TaskError> {
+    async move {
+        let mut checked_until = Utc::now();
+        loop {
+            let next = state
+                .page_states
+                .read()
+                .await
+                .values()
+                .filter_map(|page_state| next_transition(&page_state.events, checked_until))
+                .min();
+            let until_next = next.map_or(TIMED_EVENTS_MAX_SLEEP, |next| {
+                (next - Utc::now()).to_std().unwrap_or_default()
+            });
+            tokio::select! {
+                _ = sleep(until_next.min(TIMED_EVENTS_MAX_SLEEP)) => {}
+                _ = state.timed_events_changed.notified() => {}
+            }
+
+            let now = Utc::now();
+            let read_lock = state.page_states.read().await;
+            for (page_name, page_state) in read_lock.iter() {
+                for message in transitions(&page_state.events, checked_until, now) {
+                    info!(page_name, ?message, "Timed event");
+                    // Sending only fails if nobody is subscribed, in which case there's nobody to
+                    // tell.
+                    let _ = state.channels[page_name].send(PageUpdate::Announcement(message));
+                }
+            }
+            drop(read_lock);
+            checked_until = now;
+            state.tasks_health.timed_events.record_success();
+        }
+    }
+    .instrument(info_span!("timed_events_task"))
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::time::Duration;
+
+    use chrono::{TimeZone, Utc};
+
+    use crate::health::TaskHealth;
+    use crate::tasks::{Backoff, TaskError, supervise, until_next_summary};
+
+    const TEST_BACKOFF: Backoff = Backoff {
+        initial: Duration::from_millis(1),
+        max: Duration::from_millis(4),
+        reset_after: Duration::from_secs(60),
+    };
+
+    #[tokio::test]
+    async fn restarts_until_fatal() {
+        let health = TaskHealth::new(Duration::from_secs(3));
+        let attempts = AtomicU32::new(0);
+
+        let err = supervise("test", &health, TEST_BACKOFF, || async {
+            match attempts.fetch_add(1, Ordering::SeqCst) {
+                0 => Err::<Infallible, _>(TaskError::Transient("transient".to_string())),
+                1 => panic!("panicked"),
+                _ => Err(TaskError::Fatal("fatal".to_string())),
+            }
+        })
+        .await;
+
+        assert_eq!(err, "fatal");
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+
+        let status = health.status(Utc::now());
+        assert!(!status.ok);
+        assert_eq!(status.failures, Some(3));
+        assert_eq!(status.last_error.as_deref(), Some("fatal"));
+    }
+
+    #[test]
+    fn next_summary() {
+        let before_delay = Utc.with_ymd_and_hms(2025, 10, 1, 0, 0, 30).unwrap();
+        assert_eq!(until_next_summary(before_delay), Duration::from_secs(30));
+
+        let evening = Utc.with_ymd_and_hms(2025, 10, 1, 23, 0, 0).unwrap();
+        assert_eq!(until_next_summary(evening), Duration::from_secs(60 * 61));
+    }
+}
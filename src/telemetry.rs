@@ -0,0 +1,173 @@
+use std::time::Instant;
+
+use axum::extract::{MatchedPath, Request};
+use axum::http::HeaderMap;
+use axum::middleware::Next;
+use axum::response::Response;
+use metrics::{Unit, counter, describe_counter, describe_gauge, describe_histogram, histogram};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use tracing::{Span, info_span};
+use tracing_subscriber::EnvFilter;
+
+use crate::config::{LogConfig, LogFormat};
+
+pub const WEBSOCKET_CONNECTIONS: &str = "websocket_connections";
+pub const EVENT_STREAM_CONNECTIONS: &str = "event_stream_connections";
+pub const CLICKS_TOTAL: &str = "clicks_total";
+pub const BROADCAST_LAG_EVENTS_TOTAL: &str = "broadcast_lag_events_total";
+pub const BROADCAST_LAGGED_MESSAGES_TOTAL: &str = "broadcast_lagged_messages_total";
+pub const SAVE_DURATION_SECONDS: &str = "save_duration_seconds";
+pub const SAVE_FAILURES_TOTAL: &str = "save_failures_total";
+pub const DB_INSERT_DURATION_SECONDS: &str = "db_insert_duration_seconds";
+pub const DB_INSERT_FAILURES_TOTAL: &str = "db_insert_failures_total";
+pub const TASK_FAILURES_TOTAL: &str = "task_failures_total";
+pub const HTTP_REQUESTS_TOTAL: &str = "http_requests_total";
+pub const HTTP_REQUEST_DURATION_SECONDS: &str = "http_request_duration_seconds";
+pub const CLIENT_CLOCK_SKEW_SECONDS: &str = "client_clock_skew_seconds";
+pub const WEBHOOK_DELIVERIES_TOTAL: &str = "webhook_deliveries_total";
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Histogram buckets in seconds, from 1ms to 10s (the request timeout).
+const DURATION_BUCKETS: &[f64] = &[
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Histogram buckets in seconds for clock skew, from 10ms to a day, since some clients have their
+/// clocks set to the wrong timezone.
+const CLOCK_SKEW_BUCKETS: &[f64] = &[
+    0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 5.0, 30.0, 60.0, 300.0, 3600.0, 86400.0,
+];
+
+/// Installs the global metrics recorder, returning the handle used to render the metrics in the
+/// Prometheus text format.
+pub fn install_recorder() -> PrometheusHandle {
+    let handle = PrometheusBuilder::new()
+        .set_buckets(DURATION_BUCKETS)
+        .unwrap()
+        .set_buckets_for_metric(
+            Matcher::Full(CLIENT_CLOCK_SKEW_SECONDS.to_string()),
+            CLOCK_SKEW_BUCKETS,
+        )
+        .unwrap()
+        .install_recorder()
+        .unwrap();
+
+    describe_gauge!(
+        WEBSOCKET_CONNECTIONS,
+        "Number of websocket connections currently open, per page"
+    );
+    describe_gauge!(
+        EVENT_STREAM_CONNECTIONS,
+        "Number of server-sent event streams currently open, per page"
+    );
+    describe_counter!(CLICKS_TOTAL, "Number of clicks received, per page");
+    describe_counter!(
+        BROADCAST_LAG_EVENTS_TOTAL,
+        "Number of times a websocket or event stream fell behind the broadcast channel and skipped messages"
+    );
+    describe_counter!(
+        BROADCAST_LAGGED_MESSAGES_TOTAL,
+        "Number of broadcast messages skipped by lagging websockets and event streams"
+    );
+    describe_histogram!(
+        SAVE_DURATION_SECONDS,
+        Unit::Seconds,
+        "Time taken to write the state to the save file"
+    );
+    describe_counter!(SAVE_FAILURES_TOTAL, "Number of failed saves");
+    describe_histogram!(
+        DB_INSERT_DURATION_SECONDS,
+        Unit::Seconds,
+        "Time taken to insert a batch of time series data"
+    );
+    describe_counter!(
+        DB_INSERT_FAILURES_TOTAL,
+        "Number of failed time series data inserts"
+    );
+    describe_counter!(
+        TASK_FAILURES_TOTAL,
+        "Number of times a background task has failed, per task"
+    );
+    describe_counter!(
+        HTTP_REQUESTS_TOTAL,
+        "Number of HTTP requests, per method, route and status"
+    );
+    describe_histogram!(
+        HTTP_REQUEST_DURATION_SECONDS,
+        Unit::Seconds,
+        "Time taken to respond to HTTP requests, per method, route and status. For websockets, \
+        this is only the upgrade."
+    );
+    describe_histogram!(
+        CLIENT_CLOCK_SKEW_SECONDS,
+        Unit::Seconds,
+        "Absolute difference between the clocks of websocket clients and the server, as measured \
+        by the clients, per page"
+    );
+    describe_counter!(
+        WEBHOOK_DELIVERIES_TOTAL,
+        "Number of webhook deliveries, per event and result (`delivered` or `failed` after every \
+        retry)"
+    );
+
+    handle
+}
+
+/// Middleware that records the count and latency of HTTP requests. Requests are labelled with
+/// the route they matched instead of their path, so that e.g. each page doesn't get its own
+/// series.
+pub async fn track_http_requests(request: Request, next: Next) -> Response {
+    let start = Instant::now();
+    let method = request.method().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+
+    let response = next.run(request).await;
+
+    let labels = [
+        ("method", method),
+        ("route", route),
+        ("status", response.status().as_u16().to_string()),
+    ];
+    counter!(HTTP_REQUESTS_TOTAL, &labels).increment(1);
+    histogram!(HTTP_REQUEST_DURATION_SECONDS, &labels).record(start.elapsed());
+
+    response
+}
+
+/// Installs the global `tracing` subscriber that writes logs to stderr.
+pub fn init_logging(config: &LogConfig) {
+    let filter = EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| EnvFilter::try_new(&config.level).unwrap());
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr);
+
+    match config.format {
+        LogFormat::Pretty => builder.pretty().init(),
+        LogFormat::Json => builder.json().flatten_event(true).init(),
+    }
+}
+
+/// Returns the request ID set by `SetRequestIdLayer`, or `"-"` if it's missing.
+pub fn request_id(headers: &HeaderMap) -> &str {
+    headers
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("-")
+}
+
+/// Span wrapping each HTTP request, so that everything logged while handling it includes its
+/// request ID.
+pub fn make_request_span<B>(request: &axum::http::Request<B>) -> Span {
+    info_span!(
+        "request",
+        method = %request.method(),
+        uri = %request.uri(),
+        request_id = request_id(request.headers()),
+    )
+}
@@ -0,0 +1,236 @@
+use chrono::{DateTime, TimeDelta, Utc};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::increment::{IncrementDistribution, MAX_INCREMENT_SECONDS};
+use crate::protocol::ServerMessage;
+
+/// Highest multiplier an event can have.
+const MAX_MULTIPLIER: f64 = 100.0;
+
+/// Event scheduled on a page, e.g. a "double time weekend", that changes how far clicks push the
+/// datetime from `start` until `end`. Scheduled through the admin API, and saved per page under
+/// `events` in the save file, e.g.
+/// `{"name": "Double time weekend", "start": "2026-10-24T00:00:00Z", "end": "2026-10-26T00:00:00Z",
+/// "multiplier": 2}`.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct TimedEvent {
+    pub name: String,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    /// Multiplies how far clicks push the datetime.
+    #[serde(
+        default = "default_multiplier",
+        skip_serializing_if = "is_default_multiplier"
+    )]
+    pub multiplier: f64,
+    /// Replaces the page's increment distribution, before `multiplier` is applied.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub increment: Option<IncrementDistribution>,
+}
+
+fn default_multiplier() -> f64 {
+    1.0
+}
+
+fn is_default_multiplier(multiplier: &f64) -> bool {
+    *multiplier == default_multiplier()
+}
+
+impl TimedEvent {
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.start <= now && now < self.end
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.is_empty() {
+            return Err("`name` must not be empty".to_string());
+        }
+        if self.start >= self.end {
+            return Err("`start` must be before `end`".to_string());
+        }
+        if !(0.0..=MAX_MULTIPLIER).contains(&self.multiplier) {
+            return Err(format!(
+                "`multiplier` must be between 0 and {}, got {}",
+                MAX_MULTIPLIER, self.multiplier
+            ));
+        }
+        if let Some(increment) = &self.increment {
+            increment.validate()?;
+        }
+        Ok(())
+    }
+
+    pub fn started_message(&self) -> ServerMessage {
+        ServerMessage::EventStarted {
+            name: self.name.clone(),
+            end: self.end.timestamp_millis(),
+            multiplier: self.multiplier,
+            increment: self.increment.clone(),
+        }
+    }
+
+    pub fn ended_message(&self) -> ServerMessage {
+        ServerMessage::EventEnded {
+            name: self.name.clone(),
+        }
+    }
+}
+
+/// Samples how far a click pushes the datetime, taking the events active at `now` into account.
+/// If several are active, the last one that replaces the increment distribution wins, and their
+/// multipliers are all applied. The events and `increment` must be valid.
+pub fn sample_increment(
+    events: &[TimedEvent],
+    increment: &IncrementDistribution,
+    now: DateTime<Utc>,
+    rng: &mut impl Rng,
+) -> TimeDelta {
+    let active = events.iter().filter(|event| event.is_active(now));
+    let increment = active
+        .clone()
+        .filter_map(|event| event.increment.as_ref())
+        .next_back()
+        .unwrap_or(increment);
+    let multiplier = active.map(|event| event.multiplier).product::<f64>();
+
+    let added = increment.sample(rng);
+    if multiplier == 1.0 {
+        return added;
+    }
+    let milliseconds =
+        (added.num_milliseconds() as f64 * multiplier).min(MAX_INCREMENT_SECONDS * 1000.0);
+    TimeDelta::milliseconds(milliseconds.round() as i64)
+}
+
+/// Messages announcing the events that started or ended after `from`, up to and including `to`.
+pub fn transitions(
+    events: &[TimedEvent],
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Vec<ServerMessage> {
+    let between = |datetime: DateTime<Utc>| from < datetime && datetime <= to;
+    let mut transitions: Vec<_> = events
+        .iter()
+        .flat_map(|event| {
+            let started = between(event.start).then(|| (event.start, event.started_message()));
+            let ended = between(event.end).then(|| (event.end, event.ended_message()));
+            started.into_iter().chain(ended)
+        })
+        .collect();
+    transitions.sort_by_key(|(datetime, _)| *datetime);
+    transitions
+        .into_iter()
+        .map(|(_, message)| message)
+        .collect()
+}
+
+/// The first time after `after` that one of the events starts or ends.
+pub fn next_transition(events: &[TimedEvent], after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    events
+        .iter()
+        .flat_map(|event| [event.start, event.end])
+        .filter(|&datetime| datetime > after)
+        .min()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeDelta, TimeZone, Utc};
+    use rand::SeedableRng;
+    use rand::rngs::SmallRng;
+
+    use crate::increment::IncrementDistribution;
+    use crate::protocol::ServerMessage;
+    use crate::timed_events::{TimedEvent, next_transition, sample_increment, transitions};
+
+    fn events() -> Vec<TimedEvent> {
+        vec![
+            TimedEvent {
+                name: "Double time weekend".to_string(),
+                start: Utc.with_ymd_and_hms(2026, 10, 24, 0, 0, 0).unwrap(),
+                end: Utc.with_ymd_and_hms(2026, 10, 26, 0, 0, 0).unwrap(),
+                multiplier: 2.0,
+                increment: None,
+            },
+            TimedEvent {
+                name: "Anniversary".to_string(),
+                start: Utc.with_ymd_and_hms(2026, 10, 25, 0, 0, 0).unwrap(),
+                end: Utc.with_ymd_and_hms(2026, 10, 25, 12, 0, 0).unwrap(),
+                multiplier: 1.0,
+                increment: Some(IncrementDistribution::Fixed { seconds: 60.0 }),
+            },
+        ]
+    }
+
+    #[test]
+    fn sample() {
+        let mut rng = SmallRng::seed_from_u64(1);
+        let events = events();
+        let increment = IncrementDistribution::Fixed { seconds: 1800.0 };
+        let mut sample = |hour| {
+            let now = Utc.with_ymd_and_hms(2026, 10, 23, 0, 0, 0).unwrap() + TimeDelta::hours(hour);
+            sample_increment(&events, &increment, now, &mut rng)
+        };
+
+        assert_eq!(sample(0), TimeDelta::minutes(30));
+        // Double time weekend.
+        assert_eq!(sample(24), TimeDelta::minutes(60));
+        // The anniversary replaces the increment, which is still doubled.
+        assert_eq!(sample(48), TimeDelta::minutes(2));
+        assert_eq!(sample(60), TimeDelta::minutes(60));
+        assert_eq!(sample(72), TimeDelta::minutes(30));
+    }
+
+    #[test]
+    fn schedule() {
+        let events = events();
+        let friday = Utc.with_ymd_and_hms(2026, 10, 23, 0, 0, 0).unwrap();
+        let sunday = Utc.with_ymd_and_hms(2026, 10, 25, 6, 0, 0).unwrap();
+        let monday = Utc.with_ymd_and_hms(2026, 10, 26, 0, 0, 0).unwrap();
+
+        assert_eq!(next_transition(&events, friday), Some(events[0].start));
+        assert_eq!(next_transition(&events, sunday), Some(events[1].end));
+        assert_eq!(next_transition(&events, monday), None);
+
+        assert_eq!(
+            transitions(&events, friday, sunday),
+            [events[0].started_message(), events[1].started_message()]
+        );
+        assert_eq!(
+            transitions(&events, sunday, monday),
+            [
+                events[1].ended_message(),
+                ServerMessage::EventEnded {
+                    name: "Double time weekend".to_string()
+                }
+            ]
+        );
+        assert_eq!(
+            transitions(&events, monday, monday + TimeDelta::days(1)),
+            []
+        );
+    }
+
+    #[test]
+    fn parse() {
+        let event: TimedEvent = serde_json::from_str(
+            r#"{"name": "Double time weekend", "start": "2026-10-24T00:00:00Z", "end": "2026-10-26T00:00:00Z", "multiplier": 2}"#,
+        )
+        .unwrap();
+        assert_eq!(event, events()[0]);
+        assert!(event.validate().is_ok());
+
+        let backwards = TimedEvent {
+            start: event.end,
+            end: event.start,
+            ..event.clone()
+        };
+        assert!(backwards.validate().is_err());
+        let negative = TimedEvent {
+            multiplier: -1.0,
+            ..event
+        };
+        assert!(negative.validate().is_err());
+    }
+}
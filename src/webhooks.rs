@@ -0,0 +1,603 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, NaiveDate, TimeDelta, Utc};
+use hmac::{Hmac, Mac};
+use metrics::counter;
+use rand::Rng;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use sqlx::PgPool;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+use tracing::{Instrument, error, info, info_span, warn};
+
+use crate::AppState;
+use crate::config::WebhookConfig;
+use crate::datetime::datetime_difference;
+use crate::db::{
+    delete_webhook_dead_letter, insert_webhook_dead_letter, query_webhook_dead_letters,
+};
+use crate::milestones::Milestone;
+use crate::telemetry::WEBHOOK_DELIVERIES_TOTAL;
+
+/// How long to wait for a webhook to respond before the attempt counts as failed.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Delays before each retry of a failed delivery. Deliveries that still fail after the last one
+/// are moved to the dead-letter table.
+pub const RETRY_DELAYS: &[Duration] = &[
+    Duration::from_secs(5),
+    Duration::from_secs(30),
+    Duration::from_secs(60 * 2),
+    Duration::from_secs(60 * 10),
+];
+
+pub const EVENT_HEADER: &str = "x-webhook-event";
+pub const DELIVERY_HEADER: &str = "x-webhook-delivery";
+/// HMAC-SHA256 of the body with the webhook's secret, as `sha256=<hex>`.
+pub const SIGNATURE_HEADER: &str = "x-webhook-signature-256";
+
+/// Kinds of events that webhooks can subscribe to.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookEventKind {
+    /// A page reached a click count or was pushed into a new year.
+    Milestone,
+    /// A page's datetime was reached.
+    Resolved,
+    /// Stats of a page over the previous day.
+    DailySummary,
+}
+
+impl WebhookEventKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            WebhookEventKind::Milestone => "milestone",
+            WebhookEventKind::Resolved => "resolved",
+            WebhookEventKind::DailySummary => "daily_summary",
+        }
+    }
+}
+
+/// Data specific to each kind of event.
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum WebhookEvent {
+    Milestone {
+        /// `click_count` or `new_year`, as in the feed.
+        kind: &'static str,
+        /// The click count or year reached.
+        value: Option<i64>,
+        /// The page's datetime at the time.
+        datetime: DateTime<Utc>,
+    },
+    Resolved {
+        datetime: DateTime<Utc>,
+    },
+    DailySummary {
+        /// The day (in UTC) that was summarized.
+        date: NaiveDate,
+        clicks_added: i64,
+        /// How far the clicks pushed the datetime.
+        seconds_added: f64,
+        /// Most users connected at once.
+        peak_user_count: i32,
+    },
+}
+
+impl WebhookEvent {
+    pub fn from_milestone(milestone: Milestone, datetime: DateTime<Utc>) -> Self {
+        match milestone {
+            Milestone::Resolved => WebhookEvent::Resolved { datetime },
+            _ => WebhookEvent::Milestone {
+                kind: milestone.kind(),
+                value: milestone.value(),
+                datetime,
+            },
+        }
+    }
+
+    pub fn kind(&self) -> WebhookEventKind {
+        match self {
+            WebhookEvent::Milestone { .. } => WebhookEventKind::Milestone,
+            WebhookEvent::Resolved { .. } => WebhookEventKind::Resolved,
+            WebhookEvent::DailySummary { .. } => WebhookEventKind::DailySummary,
+        }
+    }
+
+    /// Description of the event, for `WebhookPayload::message`.
+    pub fn message(&self, page_title: &str) -> String {
+        match self {
+            WebhookEvent::Milestone { kind, value, .. } => Milestone::from_kind(kind, *value)
+                .map(|milestone| milestone.title(page_title))
+                .unwrap_or_default(),
+            WebhookEvent::Resolved { .. } => Milestone::Resolved.title(page_title),
+            WebhookEvent::DailySummary {
+                date,
+                clicks_added,
+                seconds_added,
+                peak_user_count,
+            } => daily_summary_message(
+                page_title,
+                *date,
+                *clicks_added,
+                *seconds_added,
+                *peak_user_count,
+            ),
+        }
+    }
+}
+
+/// Description of a page's day, e.g. `BattleBit Remastered got 120 clicks on 2025-10-01, pushing
+/// the datetime forward by 2d 12h 0m 0s, with up to 14 users at once`, or "pulling the datetime
+/// back" if hope mode subtracted more than the clicks added. Also used by the feed.
+pub fn daily_summary_message(
+    page_title: &str,
+    date: NaiveDate,
+    clicks_added: i64,
+    seconds_added: f64,
+    peak_user_count: i32,
+) -> String {
+    let epoch = DateTime::UNIX_EPOCH.naive_utc();
+    let millis_added = (seconds_added * 1000.0) as i64;
+    let moved = datetime_difference(epoch, epoch + TimeDelta::milliseconds(millis_added));
+    let direction = if millis_added < 0 {
+        "pulling the datetime back"
+    } else {
+        "pushing the datetime forward"
+    };
+    format!(
+        "{} got {} clicks on {}, {} by {}, with up to {} users at once",
+        page_title, clicks_added, date, direction, moved, peak_user_count
+    )
+}
+
+/// JSON body sent to webhooks.
+#[derive(Serialize, Debug, Clone)]
+pub struct WebhookPayload {
+    pub page: String,
+    pub title: String,
+    /// Description of the event, e.g. `BattleBit Remastered reached 10000 clicks`, which can be
+    /// posted to a chat as is.
+    pub message: String,
+    pub timestamp: DateTime<Utc>,
+    #[serde(flatten)]
+    pub event: WebhookEvent,
+}
+
+/// Queue of events for `webhook_worker` to deliver.
+pub struct WebhookQueue {
+    tx: mpsc::UnboundedSender<WebhookPayload>,
+}
+
+impl WebhookQueue {
+    pub fn new() -> (Self, mpsc::UnboundedReceiver<WebhookPayload>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        (Self { tx }, rx)
+    }
+
+    pub fn push(&self, payload: WebhookPayload) {
+        // Only fails once the worker has stopped, i.e. when shutting down.
+        let _ = self.tx.send(payload);
+    }
+}
+
+/// Signature of the body, sent in `SIGNATURE_HEADER`.
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(body);
+    let hex = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<String>();
+    format!("sha256={}", hex)
+}
+
+/// Random ID of a delivery, which stays the same across retries.
+fn new_delivery_id() -> String {
+    format!("{:032x}", rand::rng().random::<u128>())
+}
+
+pub fn http_client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .user_agent(concat!("update-countdown/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .unwrap()
+}
+
+/// Delivery that failed every attempt.
+#[derive(Debug)]
+pub struct DeliveryError {
+    pub attempts: i32,
+    pub last_error: String,
+}
+
+/// Sends the body to the webhook, retrying after each of `retry_delays` if it fails. Client
+/// errors other than `408 Request Timeout` and `429 Too Many Requests` aren't retried, since they
+/// would fail again. Gives up instead of waiting to retry once `shutdown` is cancelled.
+pub async fn deliver(
+    client: &reqwest::Client,
+    webhook: &WebhookConfig,
+    event: &str,
+    delivery_id: &str,
+    body: &[u8],
+    retry_delays: &[Duration],
+    shutdown: &CancellationToken,
+) -> Result<(), DeliveryError> {
+    let mut attempts = 0;
+    let mut delays = retry_delays.iter();
+    loop {
+        attempts += 1;
+        let mut request = client
+            .post(&webhook.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(EVENT_HEADER, event)
+            .header(DELIVERY_HEADER, delivery_id)
+            .body(body.to_vec());
+        if let Some(secret) = &webhook.secret {
+            request = request.header(SIGNATURE_HEADER, sign(secret, body));
+        }
+
+        let (last_error, retryable) = match request.send().await {
+            Ok(response) if response.status().is_success() => return Ok(()),
+            Ok(response) => {
+                let status = response.status();
+                let retryable = !status.is_client_error()
+                    || status == StatusCode::REQUEST_TIMEOUT
+                    || status == StatusCode::TOO_MANY_REQUESTS;
+                (format!("responded with {}", status), retryable)
+            }
+            Err(err) => (err.to_string(), true),
+        };
+
+        match delays.next() {
+            Some(delay) if retryable && !shutdown.is_cancelled() => {
+                warn!(attempts, last_error, retry_in = ?delay, "Webhook delivery failed");
+                tokio::select! {
+                    _ = tokio::time::sleep(*delay) => {}
+                    _ = shutdown.cancelled() => {
+                        return Err(DeliveryError {
+                            attempts,
+                            last_error: format!("{} (shut down before retrying)", last_error),
+                        });
+                    }
+                }
+            }
+            _ => {
+                return Err(DeliveryError {
+                    attempts,
+                    last_error,
+                });
+            }
+        }
+    }
+}
+
+/// Webhooks subscribed to events of this kind.
+fn subscribed_webhooks(
+    webhooks: &[WebhookConfig],
+    kind: WebhookEventKind,
+) -> impl Iterator<Item = &WebhookConfig> {
+    webhooks
+        .iter()
+        .filter(move |webhook| webhook.events.is_empty() || webhook.events.contains(&kind))
+}
+
+/// Delivers queued events to every webhook subscribed to them, each in its own task (tracked by
+/// `AppState::webhook_deliveries`) so that a slow webhook doesn't hold up the others. Deliveries
+/// that fail every attempt are stored in the dead-letter table, and so are events still queued
+/// when the server shuts down, so that they can be sent with the `retry-webhooks` subcommand.
+pub async fn webhook_worker(state: Arc<AppState>, mut rx: mpsc::UnboundedReceiver<WebhookPayload>) {
+    let client = http_client();
+    loop {
+        let payload = tokio::select! {
+            payload = rx.recv() => match payload {
+                Some(payload) => payload,
+                None => break,
+            },
+            _ = state.shutdown.cancelled() => break,
+        };
+        let kind = payload.event.kind();
+        let body = serde_json::to_vec(&payload).unwrap();
+        for webhook in subscribed_webhooks(&state.config.webhooks, kind) {
+            let span = info_span!("webhook", url = webhook.url, event = kind.as_str());
+            let state = state.clone();
+            let client = client.clone();
+            let webhook = webhook.clone();
+            let body = body.clone();
+            state.webhook_deliveries.clone().spawn(
+                async move {
+                    let delivery_id = new_delivery_id();
+                    let result = deliver(
+                        &client,
+                        &webhook,
+                        kind.as_str(),
+                        &delivery_id,
+                        &body,
+                        RETRY_DELAYS,
+                        &state.shutdown,
+                    )
+                    .await;
+                    record_delivery(
+                        &state.db_pool,
+                        &webhook,
+                        kind.as_str(),
+                        &delivery_id,
+                        &body,
+                        result,
+                    )
+                    .await;
+                }
+                .instrument(span),
+            );
+        }
+    }
+
+    rx.close();
+    while let Ok(payload) = rx.try_recv() {
+        let kind = payload.event.kind();
+        let body = serde_json::to_vec(&payload).unwrap();
+        for webhook in subscribed_webhooks(&state.config.webhooks, kind) {
+            let result = Err(DeliveryError {
+                attempts: 0,
+                last_error: "shut down before delivering".to_string(),
+            });
+            record_delivery(
+                &state.db_pool,
+                webhook,
+                kind.as_str(),
+                &new_delivery_id(),
+                &body,
+                result,
+            )
+            .await;
+        }
+    }
+}
+
+/// Logs the result of a delivery, storing it in the dead-letter table if it failed.
+async fn record_delivery(
+    pool: &PgPool,
+    webhook: &WebhookConfig,
+    event: &str,
+    delivery_id: &str,
+    body: &[u8],
+    result: Result<(), DeliveryError>,
+) {
+    let err = match result {
+        Ok(()) => {
+            counter!(WEBHOOK_DELIVERIES_TOTAL, "event" => event.to_string(), "result" => "delivered")
+                .increment(1);
+            info!(delivery_id, "Webhook delivered");
+            return;
+        }
+        Err(err) => err,
+    };
+
+    counter!(WEBHOOK_DELIVERIES_TOTAL, "event" => event.to_string(), "result" => "failed")
+        .increment(1);
+    error!(
+        delivery_id,
+        attempts = err.attempts,
+        err.last_error,
+        "Webhook delivery failed, giving up"
+    );
+    let dead_letter = DeadLetter {
+        timestamp: Utc::now(),
+        url: webhook.url.clone(),
+        event: event.to_string(),
+        delivery_id: delivery_id.to_string(),
+        payload: String::from_utf8_lossy(body).into_owned(),
+        attempts: err.attempts,
+        last_error: err.last_error,
+    };
+    if let Err(db_err) = insert_webhook_dead_letter(pool, &dead_letter).await {
+        error!(%db_err, delivery_id, "Failed to store webhook dead letter");
+    }
+}
+
+/// A delivery stored in the dead-letter table.
+pub struct DeadLetter {
+    /// When the last attempt failed.
+    pub timestamp: DateTime<Utc>,
+    pub url: String,
+    pub event: String,
+    pub delivery_id: String,
+    /// JSON body of the request.
+    pub payload: String,
+    pub attempts: i32,
+    pub last_error: String,
+}
+
+/// Attempts every dead letter once more, removing the ones that are delivered. Dead letters of
+/// webhooks that are no longer in the config are left alone. Returns the number delivered and the
+/// number that failed again. Used by the `retry-webhooks` subcommand.
+pub async fn retry_dead_letters(
+    pool: &PgPool,
+    webhooks: &[WebhookConfig],
+) -> Result<(usize, usize), sqlx::Error> {
+    let client = http_client();
+    let (mut delivered, mut failed) = (0, 0);
+    for (id, dead_letter) in query_webhook_dead_letters(pool).await? {
+        let Some(webhook) = webhooks
+            .iter()
+            .find(|webhook| webhook.url == dead_letter.url)
+        else {
+            continue;
+        };
+        let result = deliver(
+            &client,
+            webhook,
+            &dead_letter.event,
+            &dead_letter.delivery_id,
+            dead_letter.payload.as_bytes(),
+            &[],
+            &CancellationToken::new(),
+        )
+        .await;
+        match result {
+            Ok(()) => {
+                delete_webhook_dead_letter(pool, id).await?;
+                delivered += 1;
+            }
+            Err(err) => {
+                warn!(
+                    delivery_id = dead_letter.delivery_id,
+                    err.last_error, "Webhook delivery failed again"
+                );
+                failed += 1;
+            }
+        }
+    }
+    Ok((delivered, failed))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use axum::Router;
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::routing::post;
+    use chrono::NaiveDate;
+    use tokio_util::sync::CancellationToken;
+
+    use crate::config::WebhookConfig;
+    use crate::webhooks::{SIGNATURE_HEADER, daily_summary_message, deliver, http_client, sign};
+
+    #[test]
+    fn signature() {
+        assert_eq!(
+            sign("key", b"The quick brown fox jumps over the lazy dog"),
+            "sha256=f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+        );
+    }
+
+    #[test]
+    fn daily_summary() {
+        let date = NaiveDate::from_ymd_opt(2025, 10, 1).unwrap();
+        assert_eq!(
+            daily_summary_message("BattleBit Remastered", date, 120, 216_000.0, 14),
+            "BattleBit Remastered got 120 clicks on 2025-10-01, pushing the datetime forward by \
+            2d 12h 0m 0s, with up to 14 users at once"
+        );
+        assert_eq!(
+            daily_summary_message("BattleBit Remastered", date, 120, -5400.5, 14),
+            "BattleBit Remastered got 120 clicks on 2025-10-01, pulling the datetime back by \
+            1h 30m 0.500s, with up to 14 users at once"
+        );
+    }
+
+    /// Starts a local webhook that responds with each of `statuses` in order (then `200 OK`),
+    /// returning its URL and the signatures it received.
+    async fn stub_webhook(statuses: Vec<StatusCode>) -> (String, Arc<Mutex<Vec<String>>>) {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let statuses = Arc::new(Mutex::new(statuses.into_iter()));
+        let app = Router::new().route(
+            "/hook",
+            post({
+                let received = received.clone();
+                move |headers: HeaderMap| async move {
+                    let signature = headers[SIGNATURE_HEADER].to_str().unwrap().to_string();
+                    received.lock().unwrap().push(signature);
+                    statuses.lock().unwrap().next().unwrap_or(StatusCode::OK)
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (url, received)
+    }
+
+    #[tokio::test]
+    async fn retries_until_delivered() {
+        let (url, received) = stub_webhook(vec![
+            StatusCode::INTERNAL_SERVER_ERROR,
+            StatusCode::TOO_MANY_REQUESTS,
+        ])
+        .await;
+        let webhook = WebhookConfig {
+            url,
+            events: Vec::new(),
+            secret: Some("secret".to_string()),
+        };
+        let body = br#"{"page":"battlebit"}"#;
+        let delays = [Duration::from_millis(1); 3];
+
+        deliver(
+            &http_client(),
+            &webhook,
+            "milestone",
+            "1",
+            body,
+            &delays,
+            &CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(*received.lock().unwrap(), vec![sign("secret", body); 3]);
+    }
+
+    #[tokio::test]
+    async fn gives_up() {
+        let (url, received) = stub_webhook(vec![StatusCode::NOT_FOUND]).await;
+        let webhook = WebhookConfig {
+            url,
+            events: Vec::new(),
+            secret: Some("secret".to_string()),
+        };
+        let delays = [Duration::from_millis(1); 3];
+
+        let err = deliver(
+            &http_client(),
+            &webhook,
+            "milestone",
+            "1",
+            b"{}",
+            &delays,
+            &CancellationToken::new(),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.attempts, 1);
+        assert_eq!(received.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn gives_up_on_shutdown() {
+        let (url, received) = stub_webhook(vec![StatusCode::INTERNAL_SERVER_ERROR]).await;
+        let webhook = WebhookConfig {
+            url,
+            events: Vec::new(),
+            secret: Some("secret".to_string()),
+        };
+        let delays = [Duration::from_secs(600)];
+        let shutdown = CancellationToken::new();
+        let client = http_client();
+
+        let delivery = deliver(
+            &client,
+            &webhook,
+            "milestone",
+            "1",
+            b"{}",
+            &delays,
+            &shutdown,
+        );
+        let cancel = async {
+            // Wait for the first attempt to fail before shutting down.
+            while received.lock().unwrap().is_empty() {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+            shutdown.cancel();
+        };
+        let (result, ()) = tokio::join!(delivery, cancel);
+        assert_eq!(result.unwrap_err().attempts, 1);
+    }
+}